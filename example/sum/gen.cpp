@@ -1,3 +1,4 @@
+#include <cstdlib>
 #include <iostream>
 #include <random>
 #include <string>
@@ -19,12 +20,17 @@ void generate(int argc, char *argv[]) {
 int main(int argc, char *argv[]) {
     std::cin.tie(nullptr)->sync_with_stdio(false);
 
-    std::string seedString;
-    for (int i = 1; i < argc; ++i) {
-        seedString += argv[i];
-        seedString += " ";
+    // cptool passes a stable seed per test case
+    if (const char *seed = std::getenv("CPTOOL_SEED")) {
+        rnd.seed(std::stoull(seed));
+    } else {
+        std::string seedString;
+        for (int i = 1; i < argc; ++i) {
+            seedString += argv[i];
+            seedString += " ";
+        }
+        rnd.seed(std::hash<std::string>{}(seedString));
     }
-    rnd.seed(std::hash<std::string>{}(seedString));
 
     generate(argc, argv);
 
@@ -1,2 +1,3 @@
 pub mod problem;
 pub mod program;
+pub mod toolchain;
@@ -1,13 +1,56 @@
+pub mod solution;
 pub mod test;
 
 use super::program::Program;
-use crate::core::problem as core_problem;
+use super::toolchain::{Toolchain, ToolchainRegistry};
+use crate::core::{problem as core_problem, program as core_program};
 use anyhow::Result;
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use solution::Solution;
 use std::collections::HashMap;
-use std::thread;
 use test::Test;
 
+#[derive(Clone, Debug, Default)]
+pub struct GenerateOptions {
+    /// Keep the output directory and only regenerate test cases whose inputs changed.
+    pub incremental: bool,
+    /// Run each generator again and fail if the inputs differ,
+    /// which regenerates every test case even if `incremental`.
+    pub check_determinism: Option<DeterminismCheck>,
+    /// Maximum number of programs compiled or test cases generated in parallel.
+    pub jobs: usize,
+}
+
+/// Working directory of the second run of generators when checking determinism.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DeterminismCheck {
+    /// The same working directory as the first run.
+    SameDir,
+    /// Another working directory, which catches generators depending on their directory.
+    OtherDir,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FileIo {
+    pub input: String,
+    pub output: String,
+}
+
+impl FileIo {
+    pub fn generate(&self) -> Result<core_problem::FileIo> {
+        for name in [&self.input, &self.output] {
+            if std::path::Path::new(name).file_name() != Some(std::ffi::OsStr::new(name)) {
+                return Err(anyhow::anyhow!("invalid file name `{}` of file io", name));
+            }
+        }
+        Ok(core_problem::FileIo {
+            input: self.input.clone(),
+            output: self.output.clone(),
+        })
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Problem {
     pub name: String,
@@ -19,37 +62,65 @@ pub struct Problem {
     pub validator_name: Option<String>,
     #[serde(rename = "checker")]
     pub checker_name: Option<String>,
+    /// Interactor of interactive problems, whose stdin and stdout are connected to the solution.
+    #[serde(rename = "interactor")]
+    pub interactor_name: Option<String>,
+    /// Solutions read from and write to these files instead of stdin and stdout.
+    #[serde(default)]
+    pub file_io: Option<FileIo>,
+    /// Solutions judged by `cptool test` (program name -> solution).
+    #[serde(default)]
+    pub solutions: HashMap<String, Solution>,
+    #[serde(default)]
+    pub toolchains: HashMap<String, Toolchain>,
+    /// Seed from which the seeds of test cases are derived, default to 0.
+    #[serde(default)]
+    pub seed: u64,
 }
 
 impl Problem {
-    pub fn generate(&self, output_dir: &std::path::PathBuf) -> Result<core_problem::Problem> {
-        if output_dir.exists() {
-            std::fs::remove_dir_all(output_dir)?;
-        }
-        std::fs::create_dir_all(output_dir)?;
+    /// Compiles all programs into the temporary directory, at most `jobs` in parallel.
+    pub fn generate_programs(&self, jobs: usize) -> Result<HashMap<String, core_program::Program>> {
         let temp_dir = crate::utils::temp_dir();
         if temp_dir.exists() {
             std::fs::remove_dir_all(&temp_dir)?;
         }
         std::fs::create_dir_all(&temp_dir)?;
 
-        let handles = self
-            .programs
-            .iter()
-            .map(|(name, program)| {
-                let name = name.clone();
-                let program = program.clone();
-                let temp_dir = temp_dir.clone();
-                thread::spawn(move || {
-                    let program = program.generate(&name, &temp_dir)?;
-                    Ok::<_, anyhow::Error>((name, program))
-                })
-            })
-            .collect::<Vec<_>>();
-        let programs = handles
-            .into_iter()
-            .map(|handle| handle.join().unwrap())
-            .collect::<Result<HashMap<_, _>>>()?;
+        let toolchains = ToolchainRegistry::load(&self.toolchains)?;
+        crate::utils::parallel_map(jobs, self.programs.iter().collect(), |(name, program)| {
+            let program = program.generate(name, &temp_dir, &toolchains)?;
+            Ok((name.clone(), program))
+        })
+        .into_iter()
+        .collect::<Result<HashMap<_, _>>>()
+    }
+
+    /// File io of the problem, which can not be combined with an interactor.
+    pub fn generate_file_io(&self) -> Result<Option<core_problem::FileIo>> {
+        if self.file_io.is_some() && self.interactor_name.is_some() {
+            return Err(anyhow::anyhow!(
+                "file io is not supported in interactive problems"
+            ));
+        }
+        self.file_io
+            .as_ref()
+            .map(|file_io| file_io.generate())
+            .transpose()
+    }
+
+    pub fn generate(
+        &self,
+        output_dir: &std::path::PathBuf,
+        options: &GenerateOptions,
+    ) -> Result<core_problem::Problem> {
+        if output_dir.exists() && !options.incremental {
+            std::fs::remove_dir_all(output_dir)?;
+        }
+        std::fs::create_dir_all(output_dir)?;
+
+        let file_io = self.generate_file_io()?;
+        let programs = self.generate_programs(options.jobs)?;
 
         let solution = programs
             .get(&self.solution_name)
@@ -72,15 +143,42 @@ impl Problem {
                     .ok_or_else(|| anyhow::anyhow!("checker `{}` not found", checker_name))
             })
             .transpose()?;
+        let interactor = self
+            .interactor_name
+            .as_ref()
+            .map(|interactor_name| {
+                programs
+                    .get(interactor_name)
+                    .ok_or_else(|| anyhow::anyhow!("interactor `{}` not found", interactor_name))
+            })
+            .transpose()?;
+
+        let solutions = self
+            .solutions
+            .iter()
+            .map(|(name, solution)| {
+                if !programs.contains_key(name) {
+                    return Err(anyhow::anyhow!("solution `{}` not found", name));
+                }
+                Ok((name.clone(), solution.generate(&self.test.tasks)?))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
 
-        let test = self
-            .test
-            .generate(&programs, solution, validator, output_dir)?;
+        let context = test::GenerateContext {
+            programs: programs.clone(),
+            solution: solution.clone(),
+            validator: validator.cloned(),
+            interactor: interactor.cloned(),
+            file_io: file_io.clone(),
+            seed: self.seed,
+            check_determinism: options.check_determinism,
+        };
+        let test = self.test.generate(context, output_dir, options)?;
 
         let mut used_bundles = std::collections::HashSet::new();
         for task in self.test.tasks.iter() {
             for bundle_name in task.bundles.iter() {
-                if self.test.bundles.get(bundle_name).is_none() {
+                if !self.test.bundles.contains_key(bundle_name) {
                     return Err(anyhow::anyhow!("test bundle `{}` not found", bundle_name));
                 }
                 used_bundles.insert(bundle_name);
@@ -101,6 +199,11 @@ impl Problem {
             name: self.name.clone(),
             test,
             checker: checker.cloned(),
+            interactor: interactor.cloned(),
+            file_io,
+            solution_name: self.solution_name.clone(),
+            solutions,
+            programs,
         })
     }
 }
@@ -0,0 +1,63 @@
+use super::test::TestTask;
+use crate::core::problem as core_problem;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Verdict {
+    #[serde(rename = "AC")]
+    Accepted,
+    #[serde(rename = "WA")]
+    WrongAnswer,
+    #[serde(rename = "PE")]
+    PresentationError,
+    #[serde(rename = "PC")]
+    PartiallyCorrect,
+    #[serde(rename = "TLE")]
+    TimeLimitExceeded,
+    #[serde(rename = "MLE")]
+    MemoryLimitExceeded,
+    #[serde(rename = "OLE")]
+    OutputLimitExceeded,
+    #[serde(rename = "RE")]
+    RuntimeError,
+}
+
+impl From<Verdict> for core_problem::test::Verdict {
+    fn from(val: Verdict) -> Self {
+        match val {
+            Verdict::Accepted => core_problem::test::Verdict::Accepted,
+            Verdict::WrongAnswer => core_problem::test::Verdict::WrongAnswer,
+            Verdict::PresentationError => core_problem::test::Verdict::PresentationError,
+            Verdict::PartiallyCorrect => core_problem::test::Verdict::PartiallyCorrect,
+            Verdict::TimeLimitExceeded => core_problem::test::Verdict::TimeLimitExceeded,
+            Verdict::MemoryLimitExceeded => core_problem::test::Verdict::MemoryLimitExceeded,
+            Verdict::OutputLimitExceeded => core_problem::test::Verdict::OutputLimitExceeded,
+            Verdict::RuntimeError => core_problem::test::Verdict::RuntimeError,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Solution {
+    /// Expected verdict of each task (task name -> verdict).
+    #[serde(default)]
+    pub expected: HashMap<String, Verdict>,
+}
+
+impl Solution {
+    pub fn generate(&self, tasks: &[TestTask]) -> Result<core_problem::solution::Solution> {
+        let expected = self
+            .expected
+            .iter()
+            .map(|(task_name, &verdict)| {
+                if !tasks.iter().any(|task| &task.name == task_name) {
+                    return Err(anyhow::anyhow!("task `{}` not found", task_name));
+                }
+                Ok((task_name.clone(), verdict.into()))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        Ok(core_problem::solution::Solution { expected })
+    }
+}
@@ -1,75 +1,672 @@
-use crate::core::{problem as core_problem, program as core_program};
+use super::{DeterminismCheck, GenerateOptions};
+use crate::core::{judge, problem as core_problem, program as core_program};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
 
+/// Programs and settings used to generate test cases.
+#[derive(Clone, Debug)]
+pub struct GenerateContext {
+    pub programs: HashMap<String, core_program::Program>,
+    pub solution: core_program::Program,
+    pub validator: Option<core_program::Program>,
+    pub interactor: Option<core_program::Program>,
+    pub file_io: Option<core_problem::FileIo>,
+    /// Seed of the problem, from which the seeds of test cases are derived.
+    pub seed: u64,
+    /// Run each generator again and fail if the inputs differ.
+    pub check_determinism: Option<DeterminismCheck>,
+}
+
+/// A test case, whose input is written by a generator or given by a hand-written file,
+/// and whose answer is the output of the solution unless given by a file.
+///
+/// Generators get a seed derived from the seed of the problem and the position of the entry,
+/// as `{seed}` in the arguments and the environment variable `CPTOOL_SEED`.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TestCase {
-    #[serde(rename = "generator")]
-    pub generator_name: String,
+    #[serde(rename = "generator", default)]
+    pub generator_name: Option<String>,
+    #[serde(default)]
     pub args: Vec<String>,
+    /// Hand-written input file, instead of a generator.
+    #[serde(default)]
+    pub input: Option<std::path::PathBuf>,
+    /// Fixed answer file, with which the solution is not run.
+    #[serde(default)]
+    pub answer: Option<std::path::PathBuf>,
+    /// Number of input files written by the generator in one run, as `1.in` to `<outputs>.in`
+    /// in its working directory, each of which is a test case.
+    #[serde(default)]
+    pub outputs: Option<usize>,
+    /// Number of copies of the entry, `{index}` is replaced by the index of the copy from 0.
+    #[serde(default)]
+    pub repeat: Option<usize>,
+    /// Variables substituted as `{name}`, the entry is copied for each combination of their values.
+    /// A value `a..b` or `a..=b` stands for every integer in the range.
+    #[serde(default)]
+    pub vars: BTreeMap<String, Vec<String>>,
+    /// Arguments split at their `{seed}` placeholders when expanded,
+    /// so that the substituted values are not searched for `{seed}` again.
+    #[serde(skip)]
+    seed_parts: Vec<Vec<String>>,
 }
 
 impl std::fmt::Display for TestCase {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{} (args: `{}`)",
-            self.generator_name,
-            self.args.join(" ")
-        )
+        match (&self.generator_name, &self.input) {
+            (Some(generator_name), _) => {
+                write!(f, "{} (args: `{}`)", generator_name, self.args.join(" "))?
+            }
+            (None, Some(input)) => write!(f, "input `{}`", input.display())?,
+            (None, None) => write!(f, "empty")?,
+        }
+        if let Some(answer) = &self.answer {
+            write!(f, " (answer: `{}`)", answer.display())?;
+        }
+        if let Some(outputs) = self.outputs {
+            write!(f, " (outputs: {})", outputs)?;
+        }
+        Ok(())
     }
 }
 
+/// Where the input of a test case comes from.
+enum InputSource<'a> {
+    Generator(&'a str),
+    File(&'a std::path::Path),
+}
+
 impl TestCase {
-    pub fn generate(
+    /// Expands `repeat` and `vars` into entries, substituting the placeholders
+    /// in the arguments and the file paths.
+    fn expand(&self) -> Result<Vec<TestCase>> {
+        let mut combinations = vec![BTreeMap::new()];
+        for (name, values) in self.vars.iter() {
+            if name == "index" || name == "seed" {
+                return Err(anyhow::anyhow!(
+                    "variable `{}` is reserved in test case `{}`",
+                    name,
+                    self
+                ));
+            }
+            let mut expanded = vec![];
+            for value in values.iter() {
+                match expand_range(value).with_context(|| {
+                    format!("invalid value of `{}` in test case `{}`", name, self)
+                })? {
+                    Some(range) => expanded.extend(range),
+                    None => expanded.push(value.clone()),
+                }
+            }
+            let values = expanded;
+            if values.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "variable `{}` has no values in test case `{}`",
+                    name,
+                    self
+                ));
+            }
+            if combinations.len().saturating_mul(values.len()) > MAX_EXPANDED_CASES {
+                return Err(anyhow::anyhow!(
+                    "test case `{}` expands into more than {} entries",
+                    self,
+                    MAX_EXPANDED_CASES
+                ));
+            }
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.insert(name.as_str(), value.clone());
+                        combination
+                    })
+                })
+                .collect();
+        }
+        let repeat = self.repeat.unwrap_or(1);
+        if repeat == 0 {
+            return Err(anyhow::anyhow!("test case `{}` is repeated 0 times", self));
+        }
+        if combinations.len().saturating_mul(repeat) > MAX_EXPANDED_CASES {
+            return Err(anyhow::anyhow!(
+                "test case `{}` expands into more than {} entries",
+                self,
+                MAX_EXPANDED_CASES
+            ));
+        }
+        let paths = self.input.iter().chain(self.answer.iter());
+        for text in self
+            .args
+            .iter()
+            .map(|arg| arg.as_str())
+            .chain(paths.map(|path| path.to_str().unwrap_or_default()))
+        {
+            // `{seed}` is substituted when the test case is generated
+            if let Some((_, name)) = placeholders(text).find(|&(_, name)| {
+                name != "index" && name != "seed" && !self.vars.contains_key(name)
+            }) {
+                return Err(anyhow::anyhow!(
+                    "unknown placeholder `{{{}}}` in test case `{}`",
+                    name,
+                    self
+                ));
+            }
+        }
+        let mut cases = vec![];
+        for combination in combinations {
+            for index in 0..repeat {
+                let index = index.to_string();
+                let substitute = |text: &str| {
+                    substitute_placeholders(text, |name| match name {
+                        "index" => Some(index.as_str()),
+                        name => combination.get(name).map(|value| value.as_str()),
+                    })
+                };
+                let substitute_path =
+                    |path: &std::path::PathBuf| substitute(&path.to_string_lossy()).into();
+                let seed_parts: Vec<Vec<String>> = self
+                    .args
+                    .iter()
+                    .map(|arg| split_seed(arg).into_iter().map(substitute).collect())
+                    .collect();
+                cases.push(TestCase {
+                    generator_name: self.generator_name.clone(),
+                    args: seed_parts
+                        .iter()
+                        .map(|parts| parts.join("{seed}"))
+                        .collect(),
+                    input: self.input.as_ref().map(substitute_path),
+                    answer: self.answer.as_ref().map(substitute_path),
+                    outputs: self.outputs,
+                    repeat: None,
+                    vars: BTreeMap::new(),
+                    seed_parts,
+                });
+            }
+        }
+        Ok(cases)
+    }
+
+    /// Arguments with `{seed}` replaced by the seed of the test case.
+    fn seeded_args(&self, seed: u64) -> Vec<String> {
+        let seed = seed.to_string();
+        self.seed_parts
+            .iter()
+            .map(|parts| parts.join(seed.as_str()))
+            .collect()
+    }
+
+    fn input_source(&self) -> Result<InputSource<'_>> {
+        if let Some(outputs) = self.outputs {
+            if self.generator_name.is_none() || self.answer.is_some() || outputs == 0 {
+                return Err(anyhow::anyhow!(
+                    "test case `{}` with outputs must have a generator, no answer file and at least one output",
+                    self
+                ));
+            }
+        }
+        match (&self.generator_name, &self.input) {
+            (Some(generator_name), None) => Ok(InputSource::Generator(generator_name)),
+            (None, Some(input)) => Ok(InputSource::File(input)),
+            _ => Err(anyhow::anyhow!(
+                "test case `{}` must have either a generator or an input file",
+                self
+            )),
+        }
+    }
+
+    /// Hash of everything the generated files depend on.
+    pub fn fingerprint(
+        &self,
+        program_fingerprints: &HashMap<String, u64>,
+        solution_fingerprint: u64,
+        validator_fingerprint: Option<u64>,
+        seed: u64,
+    ) -> Result<u64> {
+        let mut hasher = crate::utils::StableHasher::new();
+        match self.input_source()? {
+            InputSource::Generator(generator_name) => {
+                let generator_fingerprint =
+                    program_fingerprints.get(generator_name).with_context(|| {
+                        format!(
+                            "generator `{}` not found for test case `{}`",
+                            generator_name, self
+                        )
+                    })?;
+                generator_fingerprint.hash(&mut hasher);
+                self.args.hash(&mut hasher);
+                seed.hash(&mut hasher);
+                self.outputs.hash(&mut hasher);
+            }
+            InputSource::File(input) => read_case_file(input, self)?.hash(&mut hasher),
+        }
+        match &self.answer {
+            Some(answer) => read_case_file(answer, self)?.hash(&mut hasher),
+            None => solution_fingerprint.hash(&mut hasher),
+        }
+        validator_fingerprint.hash(&mut hasher);
+        Ok(hasher.finish())
+    }
+
+    /// Writes the inputs of the test cases of the entry, given the input path of each,
+    /// and returns the log of the programs run, which is saved to `logs/<case>.log`
+    /// in the temp dir for each test case on failure.
+    pub fn generate_inputs(
+        &self,
+        context: &GenerateContext,
+        input_paths: &[std::path::PathBuf],
+        seed: u64,
+    ) -> Result<core_program::RunLog> {
+        let args = self.seeded_args(seed);
+        let envs = [(core_problem::test::SEED_ENV, seed.to_string())];
+        let input_paths = input_paths
+            .iter()
+            .map(|input_path| input_path.as_path())
+            .collect::<Vec<_>>();
+        let mut log = core_program::RunLog::default();
+        if let Err(error) = self.write_inputs(context, &args, &envs, &input_paths, &mut log) {
+            log.record("error", format!("{:#}", error));
+            for input_path in input_paths {
+                save_log(&log, input_path)?;
+            }
+            return Err(error);
+        }
+        Ok(log)
+    }
+
+    /// Writes the answer of a test case of the entry whose input is written, and validates
+    /// the input. The programs run are added to `log`, which is saved to `logs/<case>.log`.
+    pub fn generate_case(
         &self,
-        programs: &HashMap<String, core_program::Program>,
-        solution: &core_program::Program,
-        validator: Option<&core_program::Program>,
-        input_path: &std::path::PathBuf,
-        answer_path: &std::path::PathBuf,
+        context: &GenerateContext,
+        input_path: &std::path::Path,
+        answer_path: &std::path::Path,
+        seed: u64,
+        mut log: core_program::RunLog,
     ) -> Result<core_problem::test::TestCase> {
-        if input_path.exists() {
-            std::fs::remove_file(input_path)?;
+        let result = self.complete(
+            context,
+            &self.seeded_args(seed),
+            input_path,
+            answer_path,
+            &mut log,
+        );
+        if let Err(error) = &result {
+            log.record("error", format!("{:#}", error));
         }
-        let input = std::fs::File::create(input_path)?;
-        let generator = programs.get(&self.generator_name).with_context(|| {
-            format!(
-                "generator `{}` not found for test case `{}`",
-                self.generator_name, self
+        save_log(&log, input_path)?;
+        result
+    }
+
+    /// Writes the input files, by a single run of the generator or from the hand-written file.
+    fn write_inputs(
+        &self,
+        context: &GenerateContext,
+        args: &[String],
+        envs: &[(&str, String)],
+        input_paths: &[&std::path::Path],
+        log: &mut core_program::RunLog,
+    ) -> Result<()> {
+        for input_path in input_paths {
+            if input_path.exists() {
+                std::fs::remove_file(input_path)?;
+            }
+        }
+        match self.input_source()? {
+            InputSource::Generator(generator_name) => {
+                let generator = context.programs.get(generator_name).with_context(|| {
+                    format!(
+                        "generator `{}` not found for test case `{}`",
+                        generator_name, self
+                    )
+                })?;
+                self.run_generator(generator, args, envs, None, input_paths, log)?;
+
+                if let Some(check) = context.check_determinism {
+                    self.check_determinism(generator, args, envs, input_paths, check, log)?;
+                }
+            }
+            InputSource::File(input) => {
+                std::fs::copy(input, input_paths[0]).with_context(|| {
+                    format!("failed to copy input file for test case `{}`", self)
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the generator again in a scratch directory and fails if the inputs differ.
+    /// The scratch directory is removed whether or not the check passes.
+    fn check_determinism(
+        &self,
+        generator: &core_program::Program,
+        args: &[String],
+        envs: &[(&str, String)],
+        input_paths: &[&std::path::Path],
+        check: DeterminismCheck,
+        log: &mut core_program::RunLog,
+    ) -> Result<()> {
+        let check_dir = crate::utils::temp_dir()
+            .join("determinism")
+            .join(input_paths[0].file_stem().unwrap_or_default());
+        clean_dir(&check_dir)?;
+        let check_paths = input_paths
+            .iter()
+            .map(|input_path| check_dir.join(input_path.file_name().unwrap_or_default()))
+            .collect::<Vec<_>>();
+        let check_paths = check_paths
+            .iter()
+            .map(|path| path.as_path())
+            .collect::<Vec<_>>();
+        let work_dir = match check {
+            DeterminismCheck::SameDir => None,
+            DeterminismCheck::OtherDir => Some(check_dir.join("work")),
+        };
+        let result = self
+            .run_generator(
+                generator,
+                args,
+                envs,
+                work_dir.as_deref(),
+                &check_paths,
+                log,
             )
-        })?;
-        generator
-            .execute(self.args.clone(), None, Some(input))
-            .with_context(|| format!("failed to generate data for test case `{}`", self))?;
-
-        let input = std::fs::File::open(input_path)?;
-        let answer = std::fs::File::create(answer_path)?;
-        solution
-            .execute(vec![], Some(input), Some(answer))
-            .with_context(|| format!("failed to generate answer for test case `{}`", self))?;
-
-        if let Some(validator) = validator {
+            .and_then(|()| {
+                for (input_path, check_path) in input_paths.iter().zip(check_paths) {
+                    if std::fs::read(input_path)? != std::fs::read(check_path)? {
+                        return Err(anyhow::anyhow!(
+                            "generator is not deterministic, `{}` differs between two runs of test case `{}`",
+                            input_path.display(),
+                            self
+                        ));
+                    }
+                }
+                Ok(())
+            });
+        std::fs::remove_dir_all(&check_dir)?;
+        result
+    }
+
+    /// Runs the generator once, writing the inputs to `input_paths`, in `work_dir` if given.
+    /// Multi-output generators run in a scratch directory named after the first test case
+    /// by default.
+    fn run_generator(
+        &self,
+        generator: &core_program::Program,
+        args: &[String],
+        envs: &[(&str, String)],
+        work_dir: Option<&std::path::Path>,
+        input_paths: &[&std::path::Path],
+        log: &mut core_program::RunLog,
+    ) -> Result<()> {
+        if self.outputs.is_none() {
+            if let Some(work_dir) = work_dir {
+                clean_dir(work_dir)?;
+            }
+            let input = std::fs::File::create(input_paths[0])?;
+            let result = generator.execute_in(args.to_vec(), work_dir, envs, None, Some(input));
+            log.record_execute(generator, args, &result);
+            result.with_context(|| format!("failed to generate data for test case `{}`", self))?;
+            if let Some(work_dir) = work_dir {
+                std::fs::remove_dir_all(work_dir)?;
+            }
+            return Ok(());
+        }
+
+        let work_dir = match work_dir {
+            Some(work_dir) => work_dir.to_path_buf(),
+            None => crate::utils::temp_dir()
+                .join("generate")
+                .join(input_paths[0].file_stem().unwrap_or_default()),
+        };
+        let work_dir = work_dir.as_path();
+        clean_dir(work_dir)?;
+        let output = std::fs::File::create(work_dir.join(".stdout"))?;
+        let result = generator.execute_in(args.to_vec(), Some(work_dir), envs, None, Some(output));
+        log.record_execute(generator, args, &result);
+        result.with_context(|| format!("failed to generate data for test case `{}`", self))?;
+        for (index, input_path) in input_paths.iter().enumerate() {
+            let file_name = format!("{}.in", index + 1);
+            std::fs::copy(work_dir.join(&file_name), input_path).with_context(|| {
+                format!(
+                    "generator did not write `{}` for test case `{}`",
+                    file_name, self
+                )
+            })?;
+        }
+        std::fs::remove_dir_all(work_dir)?;
+        Ok(())
+    }
+
+    /// Writes the answer of a test case whose input is written, and validates the input.
+    fn complete(
+        &self,
+        context: &GenerateContext,
+        args: &[String],
+        input_path: &std::path::Path,
+        answer_path: &std::path::Path,
+        log: &mut core_program::RunLog,
+    ) -> Result<core_problem::test::TestCase> {
+        let case_name = input_path.file_stem().unwrap_or_default().to_string_lossy();
+        match &self.answer {
+            Some(answer) => {
+                std::fs::copy(answer, answer_path).with_context(|| {
+                    format!("failed to copy answer file for test case `{}`", self)
+                })?;
+                println!(
+                    "test case `{}`: answer from `{}`",
+                    case_name,
+                    answer.display()
+                );
+            }
+            None => {
+                let run = judge::run_solution(
+                    &context.solution,
+                    context.interactor.as_ref(),
+                    context.file_io.as_ref(),
+                    input_path,
+                    answer_path,
+                )
+                .with_context(|| format!("failed to generate answer for test case `{}`", self))?;
+                run.record(&context.solution, log);
+                if let Some(failure) = run.failure() {
+                    return Err(anyhow::anyhow!("{}: {}", failure, context.solution)).with_context(
+                        || format!("failed to generate answer for test case `{}`", self),
+                    );
+                }
+                println!(
+                    "test case `{}`: {} ({:.0}% of time limit)",
+                    case_name,
+                    run.solution.usage,
+                    run.solution.usage.cpu_time.as_secs_f64() / context.solution.time_limit_secs
+                        * 100.0
+                );
+            }
+        }
+
+        if let Some(validator) = &context.validator {
             let input = std::fs::File::open(input_path)?;
-            validator
-                .execute(vec![], Some(input), None)
-                .with_context(|| format!("failed to validate test case `{}`", self))?;
+            let result = validator.execute(vec![], Some(input), None);
+            log.record_execute(validator, &[], &result);
+            result.with_context(|| format!("failed to validate test case `{}`", self))?;
         }
 
         Ok(core_problem::test::TestCase {
-            args: self.args.clone(),
-            input_path: input_path.clone(),
-            answer_path: answer_path.clone(),
+            args: args.to_vec(),
+            input_path: input_path.to_path_buf(),
+            answer_path: answer_path.to_path_buf(),
         })
     }
 }
 
+/// Creates an empty directory, removing the existing one.
+fn clean_dir(path: &std::path::Path) -> Result<()> {
+    if path.exists() {
+        std::fs::remove_dir_all(path)?;
+    }
+    std::fs::create_dir_all(path)?;
+    Ok(())
+}
+
+fn save_log(log: &core_program::RunLog, input_path: &std::path::Path) -> Result<()> {
+    let case_name = input_path.file_stem().unwrap_or_default().to_string_lossy();
+    log.save(
+        &crate::utils::temp_dir()
+            .join("logs")
+            .join(format!("{}.log", case_name)),
+    )
+}
+
+/// Maximum number of entries a test case expands into, to catch mistyped ranges.
+const MAX_EXPANDED_CASES: usize = 100_000;
+
+/// Integers of a range `a..b` or `a..=b`, `None` if the value is not a range.
+fn expand_range(value: &str) -> Result<Option<Vec<String>>> {
+    let (start, end, inclusive) = match value.split_once("..=") {
+        Some((start, end)) => (start, end, true),
+        None => match value.split_once("..") {
+            Some((start, end)) => (start, end, false),
+            None => return Ok(None),
+        },
+    };
+    let (Ok(start), Ok(end)) = (start.trim().parse::<i64>(), end.trim().parse::<i64>()) else {
+        return Ok(None);
+    };
+    if start > end {
+        return Err(anyhow::anyhow!("range `{}` is reversed", value));
+    }
+    let len = end.abs_diff(start) as u128 + inclusive as u128;
+    if len > MAX_EXPANDED_CASES as u128 {
+        return Err(anyhow::anyhow!(
+            "range `{}` has more than {} values",
+            value,
+            MAX_EXPANDED_CASES
+        ));
+    }
+    let values = (0..len as i64).map(|offset| (start + offset).to_string());
+    Ok(Some(values.collect()))
+}
+
+/// The `{name}` placeholders in the text, with the position of their `{`.
+fn placeholders(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.match_indices('{').filter_map(|(start, _)| {
+        let (name, _) = text[start + 1..].split_once('}')?;
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || char == '_');
+        valid.then_some((start, name))
+    })
+}
+
+/// Replaces the placeholders that have a value in a single pass,
+/// so that the substituted values are never searched for placeholders.
+fn substitute_placeholders<'a>(text: &str, value: impl Fn(&str) -> Option<&'a str>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut end = 0;
+    for (start, name) in placeholders(text) {
+        if let Some(value) = value(name) {
+            result.push_str(&text[end..start]);
+            result.push_str(value);
+            end = start + name.len() + 2;
+        }
+    }
+    result.push_str(&text[end..]);
+    result
+}
+
+/// Parts of the text between its `{seed}` placeholders.
+fn split_seed(text: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut end = 0;
+    for (start, name) in placeholders(text) {
+        if name == "seed" {
+            parts.push(&text[end..start]);
+            end = start + name.len() + 2;
+        }
+    }
+    parts.push(&text[end..]);
+    parts
+}
+
+/// Reads an input or answer file of a test case.
+fn read_case_file(path: &std::path::Path, case: &TestCase) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| {
+        format!(
+            "failed to read `{}` for test case `{}`",
+            path.display(),
+            case
+        )
+    })
+}
+
+/// Seed of the entry `index` of the bundle, stable across runs.
+///
+/// The fields are hashed as explicit little endian bytes, the name after its length, since the
+/// framing of `Hash` may change between Rust versions and the seeds must not.
+fn case_seed(problem_seed: u64, bundle_name: &str, index: usize) -> u64 {
+    let mut hasher = crate::utils::StableHasher::new();
+    hasher.write(&problem_seed.to_le_bytes());
+    hasher.write(&(bundle_name.len() as u64).to_le_bytes());
+    hasher.write(bundle_name.as_bytes());
+    hasher.write(&(index as u64).to_le_bytes());
+    hasher.finish()
+}
+
+/// A bundle of test cases, whose entries are expanded by `repeat` and `vars` when loaded.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "TestBundleConfig")]
 pub struct TestBundle {
     pub cases: Vec<TestCase>,
 }
 
+#[derive(Deserialize)]
+struct TestBundleConfig {
+    cases: Vec<TestCase>,
+}
+
+impl TryFrom<TestBundleConfig> for TestBundle {
+    type Error = anyhow::Error;
+
+    fn try_from(config: TestBundleConfig) -> Result<Self> {
+        let cases = config
+            .cases
+            .iter()
+            .map(|case| case.expand())
+            .collect::<Result<Vec<_>>>()?
+            .concat();
+        Ok(Self { cases })
+    }
+}
+
+impl TestBundle {
+    /// Names of the test cases of each entry and the seed of the entry.
+    ///
+    /// The test case of the entry `index` is named `<bundle>-<index>`, and the test cases of an
+    /// entry with `outputs` are named `<bundle>-<index>-<output>` with `<output>` from 0.
+    /// Both only depend on the position of the entry, so that changing the `outputs` of an entry
+    /// neither renames nor reseeds the others, which would regenerate them with `--incremental`.
+    fn case_names(&self, bundle_name: &str, problem_seed: u64) -> Vec<(Vec<String>, u64)> {
+        self.cases
+            .iter()
+            .enumerate()
+            .map(|(index, case)| {
+                let seed = case_seed(problem_seed, bundle_name, index);
+                let case_names = match case.outputs {
+                    Some(outputs) => (0..outputs)
+                        .map(|output| format!("{}-{}-{}", bundle_name, index, output))
+                        .collect(),
+                    None => vec![format!("{}-{}", bundle_name, index)],
+                };
+                (case_names, seed)
+            })
+            .collect()
+    }
+}
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum TestTaskType {
     #[serde(rename = "sum")]
@@ -110,6 +707,37 @@ impl TestTask {
     }
 }
 
+/// Fingerprints of the test cases in the output directory, used by incremental generation.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    cases: HashMap<String, u64>,
+}
+
+impl Manifest {
+    const FILE_NAME: &'static str = ".manifest.yaml";
+
+    fn load(output_dir: &std::path::Path) -> Result<Self> {
+        let path = output_dir.join(Self::FILE_NAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let yaml = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_yaml::from_str(&yaml).with_context(|| {
+            format!(
+                "failed to parse {}, remove it to regenerate all test cases",
+                path.display()
+            )
+        })
+    }
+
+    fn save(&self, output_dir: &std::path::Path) -> Result<()> {
+        let yaml = serde_yaml::to_string(self)?;
+        std::fs::write(output_dir.join(Self::FILE_NAME), yaml)?;
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Test {
     pub bundles: HashMap<String, TestBundle>,
@@ -119,49 +747,190 @@ pub struct Test {
 impl Test {
     pub fn generate(
         &self,
-        programs: &HashMap<String, core_program::Program>,
-        solution: &core_program::Program,
-        validator: Option<&core_program::Program>,
+        context: GenerateContext,
         output_dir: &std::path::Path,
+        options: &GenerateOptions,
     ) -> Result<core_problem::test::Test> {
-        let bundles = self
+        let old_manifest = if options.incremental {
+            Manifest::load(output_dir)?
+        } else {
+            Manifest::default()
+        };
+        let program_fingerprints = context
+            .programs
+            .iter()
+            .map(|(name, program)| Ok((name.clone(), program.fingerprint()?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        // answers depend on the interactor and file io as well
+        let solution_fingerprint = {
+            let mut hasher = crate::utils::StableHasher::new();
+            context.solution.fingerprint()?.hash(&mut hasher);
+            context
+                .interactor
+                .as_ref()
+                .map(|interactor| interactor.fingerprint())
+                .transpose()?
+                .hash(&mut hasher);
+            context
+                .file_io
+                .as_ref()
+                .map(|file_io| (&file_io.input, &file_io.output))
+                .hash(&mut hasher);
+            hasher.finish()
+        };
+        let validator_fingerprint = context
+            .validator
+            .as_ref()
+            .map(|validator| validator.fingerprint())
+            .transpose()?;
+
+        let mut manifest = Manifest::default();
+        let mut up_to_date = std::collections::HashSet::new();
+        for (bundle_name, bundle) in self.bundles.iter() {
+            for (case, (case_names, seed)) in bundle
+                .cases
+                .iter()
+                .zip(bundle.case_names(bundle_name, context.seed))
+            {
+                let fingerprint = case.fingerprint(
+                    &program_fingerprints,
+                    solution_fingerprint,
+                    validator_fingerprint,
+                    seed,
+                )?;
+                // test cases written by a single generator run are regenerated together,
+                // and all of them when checking determinism, as skipped ones would go unchecked
+                if context.check_determinism.is_none()
+                    && case_names.iter().all(|case_name| {
+                        old_manifest.cases.get(case_name) == Some(&fingerprint)
+                            && output_dir.join(format!("{}.in", case_name)).exists()
+                            && output_dir.join(format!("{}.ans", case_name)).exists()
+                    })
+                {
+                    up_to_date.extend(case_names.iter().cloned());
+                }
+                for case_name in case_names {
+                    manifest.cases.insert(case_name, fingerprint);
+                }
+            }
+        }
+        for case_name in old_manifest.cases.keys() {
+            if !manifest.cases.contains_key(case_name) {
+                for extension in ["in", "ans"] {
+                    let path = output_dir.join(format!("{}.{}", case_name, extension));
+                    if path.exists() {
+                        std::fs::remove_file(path)?;
+                    }
+                }
+            }
+        }
+        // only up-to-date cases are recorded until generation succeeds,
+        // so that an interrupted run never leaves stale files marked as valid
+        Manifest {
+            cases: manifest
+                .cases
+                .iter()
+                .filter(|(case_name, _)| up_to_date.contains(*case_name))
+                .map(|(case_name, &fingerprint)| (case_name.clone(), fingerprint))
+                .collect(),
+        }
+        .save(output_dir)?;
+        if !up_to_date.is_empty() {
+            println!("skip {} up-to-date test cases", up_to_date.len());
+        }
+
+        // the inputs of each entry are written by one job, which then queues a job for the answer
+        // of each of its test cases, with at most `jobs` parallel jobs across all bundles
+        enum Job {
+            Entry(usize),
+            Case(usize, usize, Option<core_program::RunLog>),
+        }
+        let entries = self
             .bundles
             .iter()
-            .map(|(bundle_name, bundle)| {
-                let handles = bundle
+            .flat_map(|(bundle_name, bundle)| {
+                bundle
                     .cases
                     .iter()
-                    .enumerate()
-                    .map(|(index, case)| {
-                        let case = case.clone();
-                        let case_name = format!("{}-{}", bundle_name, index);
-                        let input_path = output_dir.join(format!("{}.in", case_name));
-                        let answer_path = output_dir.join(format!("{}.ans", case_name));
-                        let programs = programs.clone();
-                        let solution = solution.clone();
-                        let validator = validator.cloned();
-                        std::thread::spawn(move || {
-                            case.generate(
-                                &programs,
-                                &solution,
-                                validator.as_ref(),
-                                &input_path,
-                                &answer_path,
-                            )
-                        })
-                    })
-                    .collect::<Vec<_>>();
-                let cases = handles
-                    .into_iter()
-                    .map(|handle| handle.join().unwrap())
-                    .collect::<Result<Vec<_>>>()?;
-
-                Ok((
+                    .zip(bundle.case_names(bundle_name, context.seed))
+                    .map(move |(case, (case_names, seed))| (bundle_name, case, case_names, seed))
+            })
+            .collect::<Vec<_>>();
+        // results are stored in the order of the test cases, at the first one of a failed entry
+        let first_slots = entries
+            .iter()
+            .scan(0, |slot, (_, _, case_names, _)| {
+                let first = *slot;
+                *slot += case_names.len();
+                Some(first)
+            })
+            .collect::<Vec<_>>();
+        let slots = std::sync::Mutex::new(
+            std::iter::repeat_with(|| None)
+                .take(entries.iter().map(|entry| entry.2.len()).sum())
+                .collect::<Vec<_>>(),
+        );
+        crate::utils::parallel_queue(
+            options.jobs,
+            (0..entries.len()).map(Job::Entry).collect(),
+            |job, push| match job {
+                Job::Entry(entry) => {
+                    let (_, case, case_names, seed) = &entries[entry];
+                    let log = if up_to_date.contains(&case_names[0]) {
+                        None
+                    } else {
+                        let input_paths = case_names
+                            .iter()
+                            .map(|case_name| output_dir.join(format!("{}.in", case_name)))
+                            .collect::<Vec<_>>();
+                        match case.generate_inputs(&context, &input_paths, *seed) {
+                            Ok(log) => Some(log),
+                            Err(error) => {
+                                slots.lock().unwrap()[first_slots[entry]] = Some(Err(error));
+                                return;
+                            }
+                        }
+                    };
+                    for output in 0..case_names.len() {
+                        push(Job::Case(entry, output, log.clone()));
+                    }
+                }
+                Job::Case(entry, output, log) => {
+                    let (_, case, case_names, seed) = &entries[entry];
+                    let input_path = output_dir.join(format!("{}.in", case_names[output]));
+                    let answer_path = output_dir.join(format!("{}.ans", case_names[output]));
+                    let result = match log {
+                        Some(log) => {
+                            case.generate_case(&context, &input_path, &answer_path, *seed, log)
+                        }
+                        None => Ok(core_problem::test::TestCase {
+                            args: case.seeded_args(*seed),
+                            input_path,
+                            answer_path,
+                        }),
+                    };
+                    slots.lock().unwrap()[first_slots[entry] + output] = Some(result);
+                }
+            },
+        );
+        let mut bundles = self
+            .bundles
+            .keys()
+            .map(|bundle_name| {
+                (
                     bundle_name.clone(),
-                    core_problem::test::TestBundle { cases },
-                ))
+                    core_problem::test::TestBundle { cases: vec![] },
+                )
             })
-            .collect::<Result<HashMap<_, _>>>()?;
+            .collect::<HashMap<_, _>>();
+        let mut slots = slots.into_inner().unwrap().into_iter();
+        for (bundle_name, _, case_names, _) in entries.iter() {
+            // the other test cases of a failed entry are empty, after its error
+            for result in slots.by_ref().take(case_names.len()).flatten() {
+                bundles.get_mut(*bundle_name).unwrap().cases.push(result?);
+            }
+        }
+        manifest.save(output_dir)?;
 
         let tasks = self
             .tasks
@@ -172,3 +941,105 @@ impl Test {
         Ok(core_problem::test::Test { bundles, tasks })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(yaml: &str) -> Result<Vec<TestCase>> {
+        serde_yaml::from_str::<TestCase>(yaml).unwrap().expand()
+    }
+
+    fn args(cases: &[TestCase]) -> Vec<String> {
+        cases.iter().map(|case| case.args.join(" ")).collect()
+    }
+
+    #[test]
+    fn expand_repeat_and_vars() {
+        let cases = expand(
+            "{generator: gen, args: ['{n}', '{m}', '{index}', '{seed}'], repeat: 2, vars: {n: [1, '5..=6'], m: [x]}}",
+        )
+        .unwrap();
+        assert_eq!(
+            args(&cases),
+            [
+                "1 x 0 {seed}",
+                "1 x 1 {seed}",
+                "5 x 0 {seed}",
+                "5 x 1 {seed}",
+                "6 x 0 {seed}",
+                "6 x 1 {seed}"
+            ]
+        );
+        assert!(cases
+            .iter()
+            .all(|case| case.repeat.is_none() && case.vars.is_empty()));
+    }
+
+    #[test]
+    fn expand_paths() {
+        let cases = expand("{input: './manual/{n}.in', vars: {n: ['1..3']}}").unwrap();
+        let inputs = cases
+            .iter()
+            .map(|case| case.input.clone().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            inputs,
+            [
+                std::path::PathBuf::from("./manual/1.in"),
+                std::path::PathBuf::from("./manual/2.in")
+            ]
+        );
+    }
+
+    #[test]
+    fn expand_errors() {
+        assert!(expand("{generator: gen, args: ['{n}']}").is_err());
+        assert!(expand("{generator: gen, args: ['{n}'], vars: {m: [1]}}").is_err());
+        assert!(expand("{generator: gen, repeat: 0}").is_err());
+        assert!(expand("{generator: gen, vars: {n: []}}").is_err());
+        assert!(expand("{generator: gen, vars: {n: ['3..1']}}").is_err());
+        assert!(expand("{generator: gen, vars: {n: ['1..1']}}").is_err());
+        assert!(expand("{generator: gen, vars: {index: [1]}}").is_err());
+        assert!(expand("{generator: gen, vars: {n: ['0..1000'], m: ['0..1000']}}").is_err());
+    }
+
+    #[test]
+    fn expand_range_values() {
+        assert_eq!(expand_range("1..4").unwrap().unwrap(), ["1", "2", "3"]);
+        assert_eq!(expand_range("-1..=1").unwrap().unwrap(), ["-1", "0", "1"]);
+        assert_eq!(expand_range("2..2").unwrap().unwrap(), Vec::<String>::new());
+        assert!(expand_range("abc").unwrap().is_none());
+        assert!(expand_range("a..b").unwrap().is_none());
+        assert!(expand_range("2..1").is_err());
+        assert!(expand_range("0..=1000000").is_err());
+        assert_eq!(
+            expand_range("9223372036854775806..=9223372036854775807")
+                .unwrap()
+                .unwrap(),
+            ["9223372036854775806", "9223372036854775807"]
+        );
+    }
+
+    #[test]
+    fn case_seeds() {
+        // seeds are part of the generated data, they must not change
+        assert_eq!(case_seed(0, "main", 0), 7601384124462305124);
+        assert_ne!(case_seed(0, "main", 0), case_seed(0, "main", 1));
+        assert_ne!(case_seed(0, "main", 0), case_seed(1, "main", 0));
+        assert_ne!(case_seed(0, "a", 0), case_seed(0, "b", 0));
+    }
+
+    #[test]
+    fn literal_braces() {
+        let cases = expand("{generator: gen, args: ['{\"a\": 1}', '{}']}").unwrap();
+        assert_eq!(args(&cases), ["{\"a\": 1} {}"]);
+        // substituted values are not searched for placeholders again
+        let cases = expand(
+            "{generator: gen, args: ['{n}', '{m}', '{seed}'], vars: {n: ['{m}{index}{seed}'], m: [x]}}",
+        )
+        .unwrap();
+        assert_eq!(args(&cases), ["{m}{index}{seed} x {seed}"]);
+        assert_eq!(cases[0].seeded_args(7), ["{m}{index}{seed}", "x", "7"]);
+    }
+}
@@ -1,5 +1,9 @@
+pub mod cache;
+
+use super::toolchain::{TemplateContext, Toolchain, ToolchainRegistry};
 use crate::core::program as core_problem;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use cache::CompileCache;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -25,12 +29,18 @@ pub struct CppProgram {
     pub path: std::path::PathBuf,
     #[serde(default = "default_compile_args")]
     pub compile_args: Vec<String>,
+    #[serde(default = "default_cpp_toolchain")]
+    pub toolchain: String,
 }
 
 fn default_compile_args() -> Vec<String> {
     vec!["-O2".to_string()]
 }
 
+fn default_cpp_toolchain() -> String {
+    "cpp".to_string()
+}
+
 impl std::fmt::Display for CppProgram {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -42,12 +52,179 @@ impl std::fmt::Display for CppProgram {
     }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PythonProgram {
+    pub path: std::path::PathBuf,
+    #[serde(default = "default_python_interpreter")]
+    pub interpreter: String,
+    #[serde(default)]
+    pub interpreter_args: Vec<String>,
+}
+
+fn default_python_interpreter() -> String {
+    "python3".to_string()
+}
+
+impl std::fmt::Display for PythonProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (interpreter: `{} {}`)",
+            self.path.display(),
+            self.interpreter,
+            self.interpreter_args.join(" ")
+        )
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RustProgram {
+    /// A single `.rs` file compiled with `rustc`, or a cargo project directory
+    /// built with `cargo build --release` (`edition` and `compile_args` are ignored).
+    pub path: std::path::PathBuf,
+    #[serde(default = "default_rust_edition")]
+    pub edition: String,
+    #[serde(default = "default_rust_compile_args")]
+    pub compile_args: Vec<String>,
+    /// Binary target of a cargo project, default to the package name in `Cargo.toml`.
+    #[serde(default)]
+    pub bin: Option<String>,
+}
+
+/// `name` in the `[package]` table of a cargo manifest, which is the default binary target.
+fn cargo_package_name(manifest_path: &std::path::Path) -> Result<String> {
+    let manifest = std::fs::read_to_string(manifest_path)?;
+    let mut in_package = false;
+    for line in manifest.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+            continue;
+        }
+        if !in_package {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            if key.trim() == "name" {
+                // a basic or literal string, possibly followed by a comment
+                let value = value.trim();
+                return value
+                    .strip_prefix('"')
+                    .and_then(|value| value.split_once('"'))
+                    .or_else(|| value.strip_prefix('\'')?.split_once('\''))
+                    .map(|(name, _)| name.to_string())
+                    .ok_or_else(|| anyhow::anyhow!("invalid package name `{}`", value));
+            }
+        }
+    }
+    Err(anyhow::anyhow!(
+        "package name not found in `{}`",
+        manifest_path.display()
+    ))
+}
+
+fn default_rust_edition() -> String {
+    "2021".to_string()
+}
+
+fn default_rust_compile_args() -> Vec<String> {
+    vec!["-O".to_string()]
+}
+
+impl std::fmt::Display for RustProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (edition: {}, compile args: `{}`)",
+            self.path.display(),
+            self.edition,
+            self.compile_args.join(" ")
+        )
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JavaProgram {
+    pub path: std::path::PathBuf,
+    /// Class containing `main`, default to the source file name.
+    #[serde(default)]
+    pub main_class: Option<String>,
+    #[serde(default)]
+    pub compile_args: Vec<String>,
+    #[serde(default)]
+    pub jvm_args: Vec<String>,
+}
+
+impl std::fmt::Display for JavaProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (compile args: `{}`, jvm args: `{}`)",
+            self.path.display(),
+            self.compile_args.join(" "),
+            self.jvm_args.join(" ")
+        )
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KotlinProgram {
+    pub path: std::path::PathBuf,
+    #[serde(default)]
+    pub compile_args: Vec<String>,
+    #[serde(default)]
+    pub jvm_args: Vec<String>,
+}
+
+impl std::fmt::Display for KotlinProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (compile args: `{}`, jvm args: `{}`)",
+            self.path.display(),
+            self.compile_args.join(" "),
+            self.jvm_args.join(" ")
+        )
+    }
+}
+
+/// A program built and run with the command templates of a toolchain.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CustomProgram {
+    pub path: std::path::PathBuf,
+    pub toolchain: String,
+    #[serde(default)]
+    pub compile_args: Vec<String>,
+}
+
+impl std::fmt::Display for CustomProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (toolchain: {}, compile args: `{}`)",
+            self.path.display(),
+            self.toolchain,
+            self.compile_args.join(" ")
+        )
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ProgramInfo {
     #[serde(rename = "command")]
     Command(CommandProgram),
     #[serde(rename = "cpp")]
     Cpp(CppProgram),
+    #[serde(rename = "python")]
+    Python(PythonProgram),
+    #[serde(rename = "rust")]
+    Rust(RustProgram),
+    #[serde(rename = "java")]
+    Java(JavaProgram),
+    #[serde(rename = "kotlin")]
+    Kotlin(KotlinProgram),
+    #[serde(rename = "custom")]
+    Custom(CustomProgram),
 }
 
 impl std::fmt::Display for ProgramInfo {
@@ -55,15 +232,64 @@ impl std::fmt::Display for ProgramInfo {
         match self {
             ProgramInfo::Command(program) => write!(f, "Command {}", program),
             ProgramInfo::Cpp(program) => write!(f, "Cpp {}", program),
+            ProgramInfo::Python(program) => write!(f, "Python {}", program),
+            ProgramInfo::Rust(program) => write!(f, "Rust {}", program),
+            ProgramInfo::Java(program) => write!(f, "Java {}", program),
+            ProgramInfo::Kotlin(program) => write!(f, "Kotlin {}", program),
+            ProgramInfo::Custom(program) => write!(f, "Custom {}", program),
         }
     }
 }
 
+fn executable_path(name: &str, output_dir: &std::path::Path) -> std::path::PathBuf {
+    let exe_name = if cfg!(windows) {
+        format!("{}.exe", name)
+    } else {
+        name.to_string()
+    };
+    output_dir.join(exe_name)
+}
+
 impl ProgramInfo {
+    fn compile(&self, command: &mut std::process::Command) -> Result<()> {
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "compile error: {}\n{}",
+                &self,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        println!("compile success: {}", &self);
+        Ok(())
+    }
+
+    fn compile_cached(&self, toolchain: &Toolchain, context: &TemplateContext) -> Result<()> {
+        let compile = context.expand(&toolchain.compile);
+        let (compiler, args) = compile
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("compile command is empty: {}", &self))?;
+        let Some(cache) = CompileCache::open() else {
+            return self.compile(std::process::Command::new(compiler).args(args));
+        };
+        let key = CompileCache::key(&toolchain.compile, context.compile_args, context.source)
+            .with_context(|| format!("failed to compile {}", &self))?;
+        if cache.fetch(key, context.executable)? {
+            println!("compile cached: {}", &self);
+            return Ok(());
+        }
+        self.compile(std::process::Command::new(compiler).args(args))?;
+        if context.executable.is_file() {
+            cache.store(key, context.executable)?;
+        }
+        Ok(())
+    }
+
     pub fn generate(
         &self,
         name: &str,
         output_dir: &std::path::Path,
+        toolchains: &ToolchainRegistry,
     ) -> Result<core_problem::ProgramInfo> {
         match &self {
             ProgramInfo::Command(CommandProgram { path, extra_args }) => Ok(
@@ -72,38 +298,202 @@ impl ProgramInfo {
                     extra_args: extra_args.clone(),
                 }),
             ),
-            ProgramInfo::Cpp(CppProgram { path, compile_args }) => {
-                let exe_name = if cfg!(windows) {
-                    format!("{}.exe", name)
-                } else {
-                    name.to_string()
+            ProgramInfo::Cpp(CppProgram {
+                path,
+                compile_args,
+                toolchain,
+            }) => {
+                let exe_path = executable_path(name, output_dir);
+                if exe_path.exists() {
+                    std::fs::remove_file(&exe_path)?;
+                }
+                let context = TemplateContext {
+                    source: path,
+                    executable: &exe_path,
+                    output_dir,
+                    compile_args,
                 };
+                self.compile_cached(toolchains.get(toolchain)?, &context)?;
 
-                let exe_path = output_dir.join(exe_name);
+                Ok(core_problem::ProgramInfo::Cpp(core_problem::CppProgram {
+                    path: exe_path,
+                    source_path: path.into(),
+                    compile_args: compile_args.clone(),
+                }))
+            }
+            ProgramInfo::Python(PythonProgram {
+                path,
+                interpreter,
+                interpreter_args,
+            }) => {
+                if !path.exists() {
+                    return Err(anyhow::anyhow!("source file not found: {}", &self));
+                }
+                Ok(core_problem::ProgramInfo::Python(
+                    core_problem::PythonProgram {
+                        path: path.into(),
+                        interpreter: interpreter.clone(),
+                        interpreter_args: interpreter_args.clone(),
+                    },
+                ))
+            }
+            ProgramInfo::Rust(RustProgram {
+                path,
+                edition,
+                compile_args,
+                bin,
+            }) => {
+                let exe_path = executable_path(name, output_dir);
                 if exe_path.exists() {
                     std::fs::remove_file(&exe_path)?;
                 }
-                let output = std::process::Command::new("g++")
-                    .arg("-o")
-                    .arg(exe_path.clone())
-                    .args(compile_args)
-                    .arg(path)
-                    .output()?;
-                if !output.status.success() {
-                    return Err(anyhow::anyhow!(
-                        "compile error: {}\n{}",
-                        &self,
-                        String::from_utf8_lossy(&output.stderr)
-                    ));
+                if path.is_dir() {
+                    let bin = match bin {
+                        Some(bin) => bin.clone(),
+                        None => {
+                            cargo_package_name(&path.join("Cargo.toml")).with_context(|| {
+                                format!("failed to read the package name of {}", &self)
+                            })?
+                        }
+                    };
+                    let target_dir = output_dir.join(format!("{}-target", name));
+                    self.compile(
+                        std::process::Command::new("cargo")
+                            .arg("build")
+                            .arg("--release")
+                            .arg("--manifest-path")
+                            .arg(path.join("Cargo.toml"))
+                            .arg("--target-dir")
+                            .arg(&target_dir)
+                            .arg("--bin")
+                            .arg(&bin),
+                    )?;
+                    std::fs::copy(
+                        executable_path(&bin, &target_dir.join("release")),
+                        &exe_path,
+                    )?;
+                } else {
+                    self.compile(
+                        std::process::Command::new("rustc")
+                            .arg("--edition")
+                            .arg(edition)
+                            .arg("-o")
+                            .arg(exe_path.clone())
+                            .args(compile_args)
+                            .arg(path),
+                    )?;
                 }
-                println!("compile success: {}", &self);
 
-                Ok(core_problem::ProgramInfo::Cpp(core_problem::CppProgram {
+                Ok(core_problem::ProgramInfo::Rust(core_problem::RustProgram {
                     path: exe_path,
                     source_path: path.into(),
+                    edition: edition.clone(),
+                    compile_args: compile_args.clone(),
+                }))
+            }
+            ProgramInfo::Java(JavaProgram {
+                path,
+                main_class,
+                compile_args,
+                jvm_args,
+            }) => {
+                let class_path = output_dir.join(format!("{}-classes", name));
+                if class_path.exists() {
+                    std::fs::remove_dir_all(&class_path)?;
+                }
+                let main_class = match main_class {
+                    Some(main_class) => main_class.clone(),
+                    None => path
+                        .file_stem()
+                        .ok_or_else(|| anyhow::anyhow!("invalid source file: {}", &self))?
+                        .to_string_lossy()
+                        .to_string(),
+                };
+                self.compile(
+                    std::process::Command::new("javac")
+                        .arg("-d")
+                        .arg(&class_path)
+                        .args(compile_args)
+                        .arg(path),
+                )?;
+
+                Ok(core_problem::ProgramInfo::Java(core_problem::JavaProgram {
+                    class_path,
+                    main_class,
+                    source_path: path.into(),
                     compile_args: compile_args.clone(),
+                    jvm_args: jvm_args.clone(),
                 }))
             }
+            ProgramInfo::Kotlin(KotlinProgram {
+                path,
+                compile_args,
+                jvm_args,
+            }) => {
+                let jar_path = output_dir.join(format!("{}.jar", name));
+                if jar_path.exists() {
+                    std::fs::remove_file(&jar_path)?;
+                }
+                self.compile(
+                    std::process::Command::new("kotlinc")
+                        .arg(path)
+                        .arg("-include-runtime")
+                        .arg("-d")
+                        .arg(&jar_path)
+                        .args(compile_args),
+                )?;
+
+                Ok(core_problem::ProgramInfo::Kotlin(
+                    core_problem::KotlinProgram {
+                        jar_path,
+                        source_path: path.into(),
+                        compile_args: compile_args.clone(),
+                        jvm_args: jvm_args.clone(),
+                    },
+                ))
+            }
+            ProgramInfo::Custom(CustomProgram {
+                path,
+                toolchain: toolchain_name,
+                compile_args,
+            }) => {
+                let toolchain = toolchains.get(toolchain_name)?;
+                let exe_path = executable_path(name, output_dir);
+                let context = TemplateContext {
+                    source: path,
+                    executable: &exe_path,
+                    output_dir,
+                    compile_args,
+                };
+                if !toolchain.compile.is_empty() {
+                    if exe_path.exists() {
+                        std::fs::remove_file(&exe_path)?;
+                    }
+                    self.compile_cached(toolchain, &context)?;
+                }
+                let command = TemplateContext {
+                    source: &crate::utils::absolute_path(path),
+                    executable: &crate::utils::absolute_path(&exe_path),
+                    output_dir: &crate::utils::absolute_path(output_dir),
+                    compile_args,
+                }
+                .expand(&toolchain.run);
+                if command.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "toolchain `{}` has no run command",
+                        toolchain_name
+                    ));
+                }
+
+                Ok(core_problem::ProgramInfo::Custom(
+                    core_problem::CustomProgram {
+                        command,
+                        source_path: path.into(),
+                        toolchain: toolchain_name.clone(),
+                        compile_args: compile_args.clone(),
+                    },
+                ))
+            }
         }
     }
 }
@@ -111,30 +501,78 @@ impl ProgramInfo {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Program {
     pub info: ProgramInfo,
+    /// CPU time limit.
     pub time_limit_secs: f64,
+    /// Wall time limit, default to [`Program::WALL_TIME_LIMIT_FACTOR`] times the CPU time limit.
+    #[serde(default)]
+    pub wall_time_limit_secs: Option<f64>,
     pub memory_limit_mb: f64,
+    /// Maximum size of files written by the program, unlimited by default.
+    #[serde(default)]
+    pub output_limit_mb: Option<f64>,
+    /// Default to the memory limit, or [`Program::JVM_STACK_LIMIT_MB`] for Java and Kotlin,
+    /// whose stack limit applies to every thread.
+    #[serde(default)]
+    pub stack_limit_mb: Option<f64>,
+    /// Run in a sandbox without network, where only the working directory is writable (linux only).
+    #[serde(default)]
+    pub sandbox: bool,
 }
 
 impl std::fmt::Display for Program {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{} (time limit: {}s, memory limit: {}MB)",
-            self.info, self.time_limit_secs, self.memory_limit_mb
-        )
+            "{} (time limit: {}s, wall time limit: {}s, memory limit: {}MB, stack limit: {}MB)",
+            self.info,
+            self.time_limit_secs,
+            self.wall_time_limit_secs(),
+            self.memory_limit_mb,
+            self.stack_limit_mb()
+        )?;
+        if let Some(output_limit_mb) = self.output_limit_mb {
+            write!(f, " (output limit: {}MB)", output_limit_mb)?;
+        }
+        if self.sandbox {
+            write!(f, " (sandboxed)")?;
+        }
+        Ok(())
     }
 }
 
 impl Program {
+    pub const WALL_TIME_LIMIT_FACTOR: f64 = 3.0;
+    pub const JVM_STACK_LIMIT_MB: f64 = 64.0;
+
+    pub fn stack_limit_mb(&self) -> f64 {
+        let default = match self.info {
+            ProgramInfo::Java(_) | ProgramInfo::Kotlin(_) => {
+                Self::JVM_STACK_LIMIT_MB.min(self.memory_limit_mb)
+            }
+            _ => self.memory_limit_mb,
+        };
+        self.stack_limit_mb.unwrap_or(default)
+    }
+
+    pub fn wall_time_limit_secs(&self) -> f64 {
+        self.wall_time_limit_secs
+            .unwrap_or(self.time_limit_secs * Self::WALL_TIME_LIMIT_FACTOR)
+    }
+
     pub fn generate(
         &self,
         name: &str,
         output_dir: &std::path::Path,
+        toolchains: &ToolchainRegistry,
     ) -> Result<core_problem::Program> {
         Ok(core_problem::Program {
-            info: self.info.generate(name, output_dir)?,
+            info: self.info.generate(name, output_dir, toolchains)?,
             time_limit_secs: self.time_limit_secs,
+            wall_time_limit_secs: self.wall_time_limit_secs(),
             memory_limit_mb: self.memory_limit_mb,
+            output_limit_mb: self.output_limit_mb,
+            stack_limit_mb: self.stack_limit_mb(),
+            sandbox: self.sandbox,
         })
     }
 }
@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Compiled executables shared across runs and problems, keyed by everything the output depends on.
+pub struct CompileCache {
+    dir: std::path::PathBuf,
+}
+
+impl CompileCache {
+    pub fn open() -> Option<Self> {
+        let dir = crate::utils::cache_dir()?.join("compile");
+        std::fs::create_dir_all(&dir).ok()?;
+        Some(Self { dir })
+    }
+
+    /// Hashes the compile command template, the compiler version, the source file and
+    /// the local headers it includes (`#include "..."`, searched in `-I` directories).
+    pub fn key(
+        compile: &[String],
+        compile_args: &[String],
+        source: &std::path::Path,
+    ) -> Result<u128> {
+        let mut hasher = KeyHasher::new();
+        hasher.write_strings(compile);
+        hasher.write_strings(compile_args);
+        if let Some(compiler) = compile.first() {
+            if let Ok(output) = std::process::Command::new(compiler)
+                .arg("--version")
+                .output()
+            {
+                hasher.write(&output.stdout);
+            }
+        }
+
+        let include_dirs = include_dirs(compile_args);
+        let mut visited = std::collections::BTreeSet::new();
+        let mut pending = vec![source.to_path_buf()];
+        while let Some(path) = pending.pop() {
+            let content = std::fs::read(&path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+            // files are hashed in discovery order, which only depends on their contents
+            hasher.write(&content);
+            for header in local_includes(&content) {
+                let base_dir = path.parent().unwrap_or(std::path::Path::new("."));
+                let found = std::iter::once(base_dir)
+                    .chain(include_dirs.iter().map(|dir| dir.as_path()))
+                    .map(|dir| dir.join(&header))
+                    .find(|path| path.is_file());
+                if let Some(found) = found {
+                    let found = found.canonicalize()?;
+                    if visited.insert(found.clone()) {
+                        pending.push(found);
+                    }
+                }
+            }
+        }
+        Ok(hasher.finish())
+    }
+
+    fn entry_path(&self, key: u128) -> std::path::PathBuf {
+        self.dir.join(format!("{:032x}", key))
+    }
+
+    /// Copies the cached executable to `exe_path`, returns whether it is cached.
+    pub fn fetch(&self, key: u128, exe_path: &std::path::Path) -> Result<bool> {
+        let entry_path = self.entry_path(key);
+        if !entry_path.is_file() {
+            return Ok(false);
+        }
+        std::fs::copy(entry_path, exe_path)?;
+        Ok(true)
+    }
+
+    pub fn store(&self, key: u128, exe_path: &std::path::Path) -> Result<()> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        // copy then rename, so that concurrent runs never see a partial executable
+        let temp_path = self.dir.join(format!(
+            "{:032x}.{}-{}.tmp",
+            key,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::copy(exe_path, &temp_path)?;
+        std::fs::rename(temp_path, self.entry_path(key))?;
+        Ok(())
+    }
+}
+
+/// 128-bit FNV-1a over explicitly framed bytes, so that cache keys only depend on the
+/// hashed contents and not on `std::hash::Hash` implementations.
+struct KeyHasher(u128);
+
+impl KeyHasher {
+    fn new() -> Self {
+        Self(0x6c62272e07bb014262b821756295c58d)
+    }
+
+    /// Writes the length before the bytes, so that consecutive fields never run into each other.
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+            self.0 ^= byte as u128;
+            self.0 = self.0.wrapping_mul(0x0000000001000000000000000000013b);
+        }
+    }
+
+    fn write_strings(&mut self, strings: &[String]) {
+        self.write(&(strings.len() as u64).to_le_bytes());
+        for string in strings {
+            self.write(string.as_bytes());
+        }
+    }
+
+    fn finish(&self) -> u128 {
+        self.0
+    }
+}
+
+fn include_dirs(compile_args: &[String]) -> Vec<std::path::PathBuf> {
+    let mut dirs = vec![];
+    let mut args = compile_args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-I" {
+            dirs.extend(args.next().map(Into::into));
+        } else if let Some(dir) = arg.strip_prefix("-I") {
+            dirs.push(dir.into());
+        }
+    }
+    dirs
+}
+
+fn local_includes(content: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(content)
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_start().strip_prefix('#')?.trim_start();
+            let line = line.strip_prefix("include")?.trim_start();
+            let line = line.strip_prefix('"')?;
+            Some(line[..line.find('"')?].to_string())
+        })
+        .collect()
+}
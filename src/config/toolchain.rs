@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Command templates of a language.
+///
+/// `{source}`, `{executable}` and `{output_dir}` are substituted inside each argument,
+/// and an argument equal to `{compile_args}` expands to the compile arguments of the program.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Toolchain {
+    /// Empty for interpreted languages.
+    #[serde(default)]
+    pub compile: Vec<String>,
+    #[serde(default = "default_run")]
+    pub run: Vec<String>,
+}
+
+fn default_run() -> Vec<String> {
+    vec!["{executable}".to_string()]
+}
+
+impl std::fmt::Display for Toolchain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "compile: `{}`, run: `{}`",
+            self.compile.join(" "),
+            self.run.join(" ")
+        )
+    }
+}
+
+pub struct TemplateContext<'a> {
+    pub source: &'a std::path::Path,
+    pub executable: &'a std::path::Path,
+    pub output_dir: &'a std::path::Path,
+    pub compile_args: &'a [String],
+}
+
+impl TemplateContext<'_> {
+    pub fn expand(&self, template: &[String]) -> Vec<String> {
+        template
+            .iter()
+            .flat_map(|arg| {
+                if arg == "{compile_args}" {
+                    return self.compile_args.to_vec();
+                }
+                vec![arg
+                    .replace("{source}", &self.source.to_string_lossy())
+                    .replace("{executable}", &self.executable.to_string_lossy())
+                    .replace("{output_dir}", &self.output_dir.to_string_lossy())]
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ToolchainRegistry {
+    toolchains: HashMap<String, Toolchain>,
+}
+
+impl ToolchainRegistry {
+    pub fn builtin() -> Self {
+        let template = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect();
+        let toolchains = HashMap::from([
+            (
+                "cpp".to_string(),
+                Toolchain {
+                    compile: template(&["g++", "-o", "{executable}", "{compile_args}", "{source}"]),
+                    run: default_run(),
+                },
+            ),
+            (
+                "c".to_string(),
+                Toolchain {
+                    compile: template(&["gcc", "-o", "{executable}", "{compile_args}", "{source}"]),
+                    run: default_run(),
+                },
+            ),
+        ]);
+        Self { toolchains }
+    }
+
+    /// Built-in toolchains, overridden by the user config file and then by `problem.yaml`.
+    pub fn load(problem_toolchains: &HashMap<String, Toolchain>) -> Result<Self> {
+        let mut registry = Self::builtin();
+        // the default file is optional, but a file given by `$CPTOOL_TOOLCHAINS` must exist
+        let explicit = std::env::var_os("CPTOOL_TOOLCHAINS").is_some();
+        if let Some(path) = crate::utils::toolchains_file().filter(|path| explicit || path.exists())
+        {
+            let yaml = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read toolchain file `{}`", path.display()))?;
+            let toolchains: HashMap<String, Toolchain> = serde_yaml::from_str(&yaml)
+                .with_context(|| format!("invalid toolchain file `{}`", path.display()))?;
+            registry.toolchains.extend(toolchains);
+        }
+        registry.toolchains.extend(problem_toolchains.clone());
+        Ok(registry)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Toolchain> {
+        self.toolchains
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("toolchain `{}` not found", name))
+    }
+}
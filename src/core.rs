@@ -1,2 +1,5 @@
+pub mod judge;
 pub mod problem;
 pub mod program;
+pub mod score;
+pub mod stress;
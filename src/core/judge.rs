@@ -0,0 +1,388 @@
+use super::problem::test::{CheckResult, TestCase, Verdict};
+use super::problem::{FileIo, Problem};
+use super::program::{ExecuteResult, ExecuteStatus, Program, ResourceUsage, RunLog};
+use super::score;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Clone, Debug)]
+pub struct CaseResult {
+    pub name: String,
+    pub check: CheckResult,
+    /// Resources used by the solution.
+    pub usage: ResourceUsage,
+}
+
+impl std::fmt::Display for CaseResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:<16} {:>4} {:>8.3}s cpu {:>8.3}s wall {:>8.1}MB",
+            self.name,
+            self.check.verdict,
+            self.usage.cpu_time.as_secs_f64(),
+            self.usage.wall_time.as_secs_f64(),
+            self.usage.memory_mb()
+        )?;
+        if !self.check.message.is_empty() {
+            write!(
+                f,
+                "  {}",
+                self.check.message.lines().next().unwrap_or_default()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TaskResult {
+    pub name: String,
+    pub cases: Vec<CaseResult>,
+    pub score: f64,
+    pub full_score: f64,
+}
+
+impl TaskResult {
+    /// Verdict of the first test case that is not accepted.
+    pub fn verdict(&self) -> Verdict {
+        self.cases
+            .iter()
+            .map(|case| case.check.verdict)
+            .find(|&verdict| verdict != Verdict::Accepted)
+            .unwrap_or(Verdict::Accepted)
+    }
+
+    /// Maximum of each resource used over the test cases.
+    pub fn max_usage(&self) -> ResourceUsage {
+        self.cases
+            .iter()
+            .fold(ResourceUsage::default(), |max, case| ResourceUsage {
+                cpu_time: max.cpu_time.max(case.usage.cpu_time),
+                wall_time: max.wall_time.max(case.usage.wall_time),
+                memory_bytes: max.memory_bytes.max(case.usage.memory_bytes),
+            })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SolutionResult {
+    pub name: String,
+    pub tasks: Vec<TaskResult>,
+}
+
+impl SolutionResult {
+    pub fn score(&self) -> f64 {
+        self.tasks.iter().map(|task| task.score).sum()
+    }
+
+    /// Describes the tasks whose verdicts deviate from the expected ones.
+    ///
+    /// A task matches the expected verdict if every test case is either accepted or
+    /// has the expected verdict, and at least one test case has the expected verdict.
+    pub fn unexpected(&self, expected: &HashMap<String, Verdict>) -> Vec<String> {
+        self.tasks
+            .iter()
+            .filter_map(|task| {
+                let &expected = expected.get(&task.name)?;
+                let matched = task.cases.iter().all(|case| {
+                    case.check.verdict == Verdict::Accepted || case.check.verdict == expected
+                }) && (expected == Verdict::Accepted
+                    || task.cases.iter().any(|case| case.check.verdict == expected));
+                (!matched).then(|| {
+                    format!(
+                        "solution `{}` on task `{}`: expected {}, found {}",
+                        self.name,
+                        task.name,
+                        expected,
+                        task.verdict()
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for SolutionResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "solution `{}`: {:.2}", self.name, self.score())?;
+        for task in self.tasks.iter() {
+            let usage = task.max_usage();
+            writeln!(
+                f,
+                "  task `{}`: {} {:.2}/{:.2} (max {:.3}s cpu, {:.3}s wall, {:.1}MB)",
+                task.name,
+                task.verdict(),
+                task.score,
+                task.full_score,
+                usage.cpu_time.as_secs_f64(),
+                usage.wall_time.as_secs_f64(),
+                usage.memory_mb()
+            )?;
+            for case in task.cases.iter() {
+                writeln!(f, "    {}", case)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Results of running a solution on a test case.
+#[derive(Clone, Debug)]
+pub struct SolutionRun {
+    pub solution: ExecuteResult,
+    pub interactor: Option<ExecuteResult>,
+}
+
+impl SolutionRun {
+    /// Records the results of the solution and its interactor.
+    pub fn record(&self, solution: &Program, log: &mut RunLog) {
+        log.record(solution, &self.solution);
+        if let Some(interactor) = &self.interactor {
+            log.record("interactor", interactor);
+        }
+    }
+
+    /// Verdict of the run if the output can not be checked.
+    pub fn failure(&self) -> Option<CheckResult> {
+        let failure = |verdict, message: String| CheckResult {
+            verdict,
+            points: 0.0,
+            message,
+        };
+        match self.solution.status {
+            ExecuteStatus::TimeLimitExceeded => {
+                return Some(failure(Verdict::TimeLimitExceeded, String::new()));
+            }
+            ExecuteStatus::MemoryLimitExceeded => {
+                return Some(failure(Verdict::MemoryLimitExceeded, String::new()));
+            }
+            ExecuteStatus::OutputLimitExceeded => {
+                return Some(failure(Verdict::OutputLimitExceeded, String::new()));
+            }
+            _ => {}
+        }
+        // the verdict of the interactor takes precedence, as the solution may be
+        // killed by a broken pipe after the interactor exits
+        if let Some(interactor) = &self.interactor {
+            if let ExecuteStatus::TimeLimitExceeded = interactor.status {
+                return Some(failure(
+                    Verdict::TimeLimitExceeded,
+                    "interactor time limit exceeded".to_string(),
+                ));
+            }
+            if !interactor.status.success() {
+                return Some(CheckResult::from_testlib(
+                    interactor.status.code(),
+                    &interactor.stderr,
+                ));
+            }
+        }
+        if !self.solution.status.success() {
+            let message = format!("{}\n{}", self.solution.status, self.solution.stderr);
+            return Some(failure(
+                Verdict::RuntimeError,
+                message.trim_end().to_string(),
+            ));
+        }
+        None
+    }
+}
+
+/// Runs the solution with file io in `scratch_dir`, see [`run_solution`].
+fn run_in_scratch_dir(
+    solution: &Program,
+    file_io: &FileIo,
+    scratch_dir: &std::path::Path,
+    input_path: &std::path::Path,
+    output_path: &std::path::Path,
+) -> Result<ExecuteResult> {
+    std::fs::create_dir_all(scratch_dir)?;
+    std::fs::copy(input_path, scratch_dir.join(&file_io.input))?;
+
+    // the input is given on stdin as well, and stdout is discarded
+    let input = std::fs::File::open(input_path)?;
+    let output = std::fs::File::create(scratch_dir.join(".stdout"))?;
+    let result = solution.run_in(vec![], Some(scratch_dir), Some(input), Some(output))?;
+    let user_output_path = scratch_dir.join(&file_io.output);
+    if user_output_path.is_file() {
+        std::fs::copy(user_output_path, output_path)?;
+    } else {
+        std::fs::File::create(output_path)?;
+    }
+    Ok(result)
+}
+
+/// Runs the solution on the input and writes its output, through the interactor if any.
+///
+/// A testlib interactor gets the input and output paths as arguments.
+/// With file io, the solution runs in a scratch directory containing the input file,
+/// and the output file is collected from it (an empty output if missing).
+pub fn run_solution(
+    solution: &Program,
+    interactor: Option<&Program>,
+    file_io: Option<&FileIo>,
+    input_path: &std::path::Path,
+    output_path: &std::path::Path,
+) -> Result<SolutionRun> {
+    if let Some(file_io) = file_io {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let scratch_dir = crate::utils::temp_dir()
+            .join("scratch")
+            .join(COUNTER.fetch_add(1, Ordering::Relaxed).to_string());
+        if scratch_dir.exists() {
+            std::fs::remove_dir_all(&scratch_dir)?;
+        }
+        // the scratch directory is removed whatever the result, an error of the run comes first
+        let result = run_in_scratch_dir(solution, file_io, &scratch_dir, input_path, output_path);
+        let removed = std::fs::remove_dir_all(&scratch_dir);
+        let result = result?;
+        removed?;
+        return Ok(SolutionRun {
+            solution: result,
+            interactor: None,
+        });
+    }
+
+    match interactor {
+        Some(interactor) => {
+            let (solution, interactor) = solution.run_interactive(
+                vec![],
+                interactor,
+                vec![
+                    input_path.to_str().unwrap().to_string(),
+                    output_path.to_str().unwrap().to_string(),
+                ],
+            )?;
+            Ok(SolutionRun {
+                solution,
+                interactor: Some(interactor),
+            })
+        }
+        None => {
+            let input = std::fs::File::open(input_path)?;
+            let output = std::fs::File::create(output_path)?;
+            Ok(SolutionRun {
+                solution: solution.run(vec![], Some(input), Some(output))?,
+                interactor: None,
+            })
+        }
+    }
+}
+
+/// Judges the solution on the test case, the run is logged to the output path with extension `log`.
+pub fn judge_case(
+    case: &TestCase,
+    solution: &Program,
+    interactor: Option<&Program>,
+    file_io: Option<&FileIo>,
+    checker: Option<&Program>,
+    output_path: &std::path::Path,
+) -> Result<CaseResult> {
+    // the log is saved before any error is returned, so that failures can be inspected
+    let mut log = RunLog::default();
+    let log_path = output_path.with_extension("log");
+    let run = match run_solution(solution, interactor, file_io, &case.input_path, output_path) {
+        Ok(run) => run,
+        Err(error) => {
+            log.record(solution, format!("{:#}", error));
+            log.save(&log_path)?;
+            return Err(error);
+        }
+    };
+    run.record(solution, &mut log);
+    let check = match run.failure() {
+        Some(check) => Ok(check),
+        None => match checker {
+            Some(checker) => case.check(output_path, checker),
+            None => case.compare(output_path),
+        },
+    };
+    match &check {
+        Ok(check) => log.record("check", check),
+        Err(error) => log.record("check", format!("{:#}", error)),
+    }
+    log.save(&log_path)?;
+    let check = check?;
+    Ok(CaseResult {
+        name: case.name(),
+        check,
+        usage: run.solution.usage,
+    })
+}
+
+/// Runs the solution on every test case one by one, so that the measured time is not disturbed.
+pub fn judge(problem: &Problem, solution_name: &str) -> Result<SolutionResult> {
+    let solution = problem
+        .programs
+        .get(solution_name)
+        .ok_or_else(|| anyhow::anyhow!("solution `{}` not found", solution_name))?;
+    let output_dir = crate::utils::temp_dir().join("judge").join(solution_name);
+    if output_dir.exists() {
+        std::fs::remove_dir_all(&output_dir)?;
+    }
+    std::fs::create_dir_all(&output_dir)?;
+
+    let mut bundle_results = HashMap::new();
+    let mut tasks = problem
+        .test
+        .tasks
+        .iter()
+        .map(|task| {
+            let mut cases = vec![];
+            for bundle_name in task.bundles.iter() {
+                let bundle =
+                    problem.test.bundles.get(bundle_name).ok_or_else(|| {
+                        anyhow::anyhow!("test bundle `{}` not found", bundle_name)
+                    })?;
+                if !bundle_results.contains_key(bundle_name) {
+                    let results = bundle
+                        .cases
+                        .iter()
+                        .map(|case| {
+                            let output_path = output_dir.join(format!("{}.out", case.name()));
+                            judge_case(
+                                case,
+                                solution,
+                                problem.interactor.as_ref(),
+                                problem.file_io.as_ref(),
+                                problem.checker.as_ref(),
+                                &output_path,
+                            )
+                            .with_context(|| {
+                                format!(
+                                    "failed to judge solution `{}` on test case `{}`",
+                                    solution_name,
+                                    case.name()
+                                )
+                            })
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    bundle_results.insert(bundle_name.clone(), results);
+                }
+                cases.extend(bundle_results[bundle_name].iter().cloned());
+            }
+            Ok(TaskResult {
+                name: task.name.clone(),
+                cases,
+                score: 0.0,
+                full_score: task.score,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let points = tasks
+        .iter()
+        .map(|task| task.cases.iter().map(|case| case.check.points).collect())
+        .collect::<Vec<_>>();
+    let scores = score::evaluate(&problem.test.tasks, &points)?;
+    for (task, score) in tasks.iter_mut().zip(scores) {
+        task.score = score;
+    }
+
+    Ok(SolutionResult {
+        name: solution_name.to_string(),
+        tasks,
+    })
+}
@@ -1,10 +1,25 @@
+pub mod solution;
 pub mod test;
 
 use crate::core::program::Program;
+use solution::Solution;
+use std::collections::HashMap;
 use test::Test;
 
+/// Names of the files a solution reads its input from and writes its output to.
+#[derive(Clone, Debug)]
+pub struct FileIo {
+    pub input: String,
+    pub output: String,
+}
+
 pub struct Problem {
     pub name: String,
+    pub programs: HashMap<String, Program>,
+    pub solution_name: String,
+    pub solutions: HashMap<String, Solution>,
     pub test: Test,
     pub checker: Option<Program>,
+    pub interactor: Option<Program>,
+    pub file_io: Option<FileIo>,
 }
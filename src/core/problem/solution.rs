@@ -0,0 +1,7 @@
+use super::test::Verdict;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug)]
+pub struct Solution {
+    pub expected: HashMap<String, Verdict>,
+}
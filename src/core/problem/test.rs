@@ -1,7 +1,99 @@
 use super::super::program::Program;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::collections::HashMap;
 
+/// Environment variable with the seed of the test case, set for generators.
+pub const SEED_ENV: &str = "CPTOOL_SEED";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Accepted,
+    WrongAnswer,
+    PresentationError,
+    PartiallyCorrect,
+    JudgementFailed,
+    TimeLimitExceeded,
+    MemoryLimitExceeded,
+    OutputLimitExceeded,
+    RuntimeError,
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Verdict::Accepted => "AC",
+            Verdict::WrongAnswer => "WA",
+            Verdict::PresentationError => "PE",
+            Verdict::PartiallyCorrect => "PC",
+            Verdict::JudgementFailed => "FAIL",
+            Verdict::TimeLimitExceeded => "TLE",
+            Verdict::MemoryLimitExceeded => "MLE",
+            Verdict::OutputLimitExceeded => "OLE",
+            Verdict::RuntimeError => "RE",
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CheckResult {
+    pub verdict: Verdict,
+    /// Ratio of the score of the test case, in `[0, 1]`.
+    pub points: f64,
+    /// Comment of the checker.
+    pub message: String,
+}
+
+impl CheckResult {
+    /// Interprets the exit code and the stderr message of a testlib checker.
+    ///
+    /// `quitp` (exit code 7) reports the points at the start of the message,
+    /// and `_pc(n)` (exit code `16 + n`) is taken as `n` percent.
+    pub fn from_testlib(exit_code: Option<i64>, message: &str) -> Self {
+        let message = message.trim().to_string();
+        let (verdict, points) = match exit_code {
+            Some(0) => (Verdict::Accepted, 1.0),
+            Some(1) => (Verdict::WrongAnswer, 0.0),
+            // wrong output format, extra information or unexpected eof
+            Some(2) | Some(4) | Some(8) => (Verdict::PresentationError, 0.0),
+            Some(7) => match message
+                .strip_prefix("points")
+                .and_then(|points| points.split_whitespace().next())
+                .and_then(|points| points.parse::<f64>().ok())
+            {
+                Some(points) => (Verdict::PartiallyCorrect, points.clamp(0.0, 1.0)),
+                None => (Verdict::JudgementFailed, 0.0),
+            },
+            Some(code) if code >= 16 => (
+                Verdict::PartiallyCorrect,
+                ((code - 16) as f64 / 100.0).clamp(0.0, 1.0),
+            ),
+            _ => (Verdict::JudgementFailed, 0.0),
+        };
+        let verdict = match verdict {
+            Verdict::PartiallyCorrect if points >= 1.0 => Verdict::Accepted,
+            verdict => verdict,
+        };
+        Self {
+            verdict,
+            points,
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.verdict {
+            Verdict::PartiallyCorrect => write!(f, "{} ({:.3})", self.verdict, self.points)?,
+            verdict => write!(f, "{}", verdict)?,
+        }
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct TestCase {
     pub args: Vec<String>,
@@ -10,19 +102,72 @@ pub struct TestCase {
 }
 
 impl TestCase {
-    // TODO: result struct
-    pub fn check(&self, output_path: &std::path::Path, checker: &Program) -> Result<()> {
-        // TODO: partial points
-        // TODO: error context
-        checker.execute(
-            vec![
-                self.input_path.to_str().unwrap().to_string(),
-                output_path.to_str().unwrap().to_string(),
-                self.answer_path.to_str().unwrap().to_string(),
-            ],
-            None,
-            None,
-        )
+    pub fn name(&self) -> String {
+        self.input_path
+            .file_stem()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Compares whitespace-separated tokens of the output and the answer, used without a checker.
+    pub fn compare(&self, output_path: &std::path::Path) -> Result<CheckResult> {
+        let output = std::fs::read_to_string(output_path)?;
+        let answer = std::fs::read_to_string(&self.answer_path)?;
+        let mut output_tokens = output.split_whitespace();
+        let mut answer_tokens = answer.split_whitespace();
+        let mut index = 1usize;
+        loop {
+            let (verdict, message) = match (output_tokens.next(), answer_tokens.next()) {
+                (None, None) => (Verdict::Accepted, format!("{} token(s)", index - 1)),
+                (Some(output), Some(answer)) if output == answer => {
+                    index += 1;
+                    continue;
+                }
+                (Some(output), Some(answer)) => (
+                    Verdict::WrongAnswer,
+                    format!(
+                        "token {} differs: expected `{}`, found `{}`",
+                        index, answer, output
+                    ),
+                ),
+                (None, Some(_)) => (Verdict::WrongAnswer, "output is too short".to_string()),
+                (Some(_), None) => (Verdict::WrongAnswer, "output is too long".to_string()),
+            };
+            let points = if verdict == Verdict::Accepted {
+                1.0
+            } else {
+                0.0
+            };
+            return Ok(CheckResult {
+                verdict,
+                points,
+                message,
+            });
+        }
+    }
+
+    pub fn check(&self, output_path: &std::path::Path, checker: &Program) -> Result<CheckResult> {
+        let result = checker
+            .run(
+                vec![
+                    self.input_path.to_str().unwrap().to_string(),
+                    output_path.to_str().unwrap().to_string(),
+                    self.answer_path.to_str().unwrap().to_string(),
+                ],
+                None,
+                None,
+            )
+            .with_context(|| {
+                format!(
+                    "failed to check output `{}` of test case `{}`",
+                    output_path.display(),
+                    self.input_path.display()
+                )
+            })?;
+        Ok(CheckResult::from_testlib(
+            result.status.code(),
+            &result.stderr,
+        ))
     }
 }
 
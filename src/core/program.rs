@@ -1,3 +1,8 @@
+#[cfg(target_os = "linux")]
+pub mod cgroup;
+pub mod process;
+pub mod sandbox;
+
 use anyhow::{Context, Result};
 
 #[derive(Clone, Debug)]
@@ -35,10 +40,115 @@ impl std::fmt::Display for CppProgram {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct PythonProgram {
+    pub path: std::path::PathBuf,
+    pub interpreter: String,
+    pub interpreter_args: Vec<String>,
+}
+
+impl std::fmt::Display for PythonProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (interpreter: `{} {}`)",
+            self.path.display(),
+            self.interpreter,
+            self.interpreter_args.join(" ")
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RustProgram {
+    pub path: std::path::PathBuf,
+    pub source_path: std::path::PathBuf,
+    pub edition: String,
+    pub compile_args: Vec<String>,
+}
+
+impl std::fmt::Display for RustProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (edition: {}, compile args: `{}`)",
+            self.source_path.display(),
+            self.edition,
+            self.compile_args.join(" ")
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct JavaProgram {
+    pub class_path: std::path::PathBuf,
+    pub main_class: String,
+    pub source_path: std::path::PathBuf,
+    pub compile_args: Vec<String>,
+    pub jvm_args: Vec<String>,
+}
+
+impl std::fmt::Display for JavaProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (compile args: `{}`, jvm args: `{}`)",
+            self.source_path.display(),
+            self.compile_args.join(" "),
+            self.jvm_args.join(" ")
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct KotlinProgram {
+    pub jar_path: std::path::PathBuf,
+    pub source_path: std::path::PathBuf,
+    pub compile_args: Vec<String>,
+    pub jvm_args: Vec<String>,
+}
+
+impl std::fmt::Display for KotlinProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (compile args: `{}`, jvm args: `{}`)",
+            self.source_path.display(),
+            self.compile_args.join(" "),
+            self.jvm_args.join(" ")
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CustomProgram {
+    pub command: Vec<String>,
+    pub source_path: std::path::PathBuf,
+    pub toolchain: String,
+    pub compile_args: Vec<String>,
+}
+
+impl std::fmt::Display for CustomProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (toolchain: {}, compile args: `{}`)",
+            self.source_path.display(),
+            self.toolchain,
+            self.compile_args.join(" ")
+        )
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum ProgramInfo {
     Command(CommandProgram),
     Cpp(CppProgram),
+    Python(PythonProgram),
+    Rust(RustProgram),
+    Java(JavaProgram),
+    Kotlin(KotlinProgram),
+    Custom(CustomProgram),
 }
 
 impl std::fmt::Display for ProgramInfo {
@@ -46,42 +156,468 @@ impl std::fmt::Display for ProgramInfo {
         match self {
             ProgramInfo::Command(program) => write!(f, "Command {}", program),
             ProgramInfo::Cpp(program) => write!(f, "Cpp {}", program),
+            ProgramInfo::Python(program) => write!(f, "Python {}", program),
+            ProgramInfo::Rust(program) => write!(f, "Rust {}", program),
+            ProgramInfo::Java(program) => write!(f, "Java {}", program),
+            ProgramInfo::Kotlin(program) => write!(f, "Kotlin {}", program),
+            ProgramInfo::Custom(program) => write!(f, "Custom {}", program),
+        }
+    }
+}
+
+impl ProgramInfo {
+    /// Programs running on the JVM reserve far more virtual memory than they use,
+    /// so their heap is limited by the JVM and their address space by a larger limit.
+    pub fn is_jvm(&self) -> bool {
+        matches!(self, ProgramInfo::Java(_) | ProgramInfo::Kotlin(_))
+    }
+
+    /// Files the behaviour of the program depends on.
+    fn files(&self) -> Vec<&std::path::Path> {
+        match self {
+            ProgramInfo::Command(CommandProgram { path, .. }) => vec![path],
+            ProgramInfo::Cpp(CppProgram { path, .. })
+            | ProgramInfo::Rust(RustProgram { path, .. })
+            | ProgramInfo::Python(PythonProgram { path, .. }) => vec![path],
+            // compiled jvm programs are not reproducible, so their sources are fingerprinted instead
+            ProgramInfo::Java(JavaProgram { source_path, .. })
+            | ProgramInfo::Kotlin(KotlinProgram { source_path, .. }) => vec![source_path],
+            ProgramInfo::Custom(CustomProgram {
+                command,
+                source_path,
+                ..
+            }) => std::iter::once(source_path.as_path())
+                .chain(command.iter().map(std::path::Path::new))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum ExecuteStatus {
+    Exited(i32),
+    /// Terminated by a signal (unix only).
+    Signaled(i32),
+    TimeLimitExceeded,
+    /// Killed for writing a file larger than the output limit (unix only).
+    OutputLimitExceeded,
+    /// Killed for exceeding the memory limit of its cgroup (linux only).
+    MemoryLimitExceeded,
+}
+
+impl ExecuteStatus {
+    pub fn success(&self) -> bool {
+        matches!(self, ExecuteStatus::Exited(0))
+    }
+
+    pub fn code(&self) -> Option<i64> {
+        match self {
+            ExecuteStatus::Exited(code) => Some(*code as i64),
+            ExecuteStatus::Signaled(_)
+            | ExecuteStatus::TimeLimitExceeded
+            | ExecuteStatus::OutputLimitExceeded
+            | ExecuteStatus::MemoryLimitExceeded => None,
         }
     }
 }
 
+impl std::fmt::Display for ExecuteStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecuteStatus::Exited(code) => write!(f, "exit code: {}", code),
+            ExecuteStatus::Signaled(signal) => match process::signal_name(*signal) {
+                Some(name) => write!(f, "signal: {} ({})", name, signal),
+                None => write!(f, "signal: {}", signal),
+            },
+            ExecuteStatus::TimeLimitExceeded => write!(f, "time limit exceeded"),
+            ExecuteStatus::OutputLimitExceeded => write!(f, "output limit exceeded"),
+            ExecuteStatus::MemoryLimitExceeded => write!(f, "memory limit exceeded"),
+        }
+    }
+}
+
+/// Resources used by a finished process.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ResourceUsage {
+    /// User and system CPU time.
+    pub cpu_time: std::time::Duration,
+    pub wall_time: std::time::Duration,
+    /// Peak resident set size.
+    pub memory_bytes: u64,
+}
+
+impl ResourceUsage {
+    pub fn memory_mb(&self) -> f64 {
+        self.memory_bytes as f64 / 1024.0 / 1024.0
+    }
+}
+
+impl std::fmt::Display for ResourceUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.3}s cpu, {:.3}s wall, {:.1}MB",
+            self.cpu_time.as_secs_f64(),
+            self.wall_time.as_secs_f64(),
+            self.memory_mb()
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ExecuteResult {
+    pub status: ExecuteStatus,
+    /// At most [`ExecuteResult::STDERR_LIMIT_BYTES`] bytes of stderr.
+    pub stderr: String,
+    pub usage: ResourceUsage,
+}
+
+impl ExecuteResult {
+    pub const STDERR_LIMIT_BYTES: usize = 16 * 1024;
+}
+
+impl std::fmt::Display for ExecuteResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.status, self.usage)?;
+        if !self.stderr.is_empty() {
+            write!(f, "\nstderr:\n{}", self.stderr.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Log of the programs run for a test case, saved to a file for debugging.
+#[derive(Clone, Debug, Default)]
+pub struct RunLog {
+    content: String,
+}
+
+impl RunLog {
+    pub fn record(&mut self, title: impl std::fmt::Display, entry: impl std::fmt::Display) {
+        use std::fmt::Write;
+        let _ = writeln!(self.content, "== {}\n{}", title, entry);
+    }
+
+    /// Records the result of [`Program::execute`], whose error contains the status and stderr.
+    pub fn record_execute(
+        &mut self,
+        program: &Program,
+        args: &[String],
+        result: &Result<ExecuteResult>,
+    ) {
+        let title = format!("{} (args: `{}`)", program, args.join(" "));
+        match result {
+            Ok(result) => self.record(title, result),
+            Err(error) => self.record(title, format!("{:#}", error)),
+        }
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, &self.content)?;
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Program {
     pub info: ProgramInfo,
+    /// CPU time limit.
     pub time_limit_secs: f64,
+    pub wall_time_limit_secs: f64,
     pub memory_limit_mb: f64,
+    /// Maximum size of files written by the program.
+    pub output_limit_mb: Option<f64>,
+    pub stack_limit_mb: f64,
+    /// Run in the sandbox, see [`sandbox`].
+    pub sandbox: bool,
 }
 
 impl std::fmt::Display for Program {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{} (time limit: {}s, memory limit: {}MB)",
-            self.info, self.time_limit_secs, self.memory_limit_mb
-        )
+            "{} (time limit: {}s, wall time limit: {}s, memory limit: {}MB, stack limit: {}MB)",
+            self.info,
+            self.time_limit_secs,
+            self.wall_time_limit_secs,
+            self.memory_limit_mb,
+            self.stack_limit_mb
+        )?;
+        if let Some(output_limit_mb) = self.output_limit_mb {
+            write!(f, " (output limit: {}MB)", output_limit_mb)?;
+        }
+        if self.sandbox {
+            write!(f, " (sandboxed)")?;
+        }
+        Ok(())
     }
 }
 
+/// Address space reserved by the jvm besides its heap, for class metadata, code and thread stacks.
+const JVM_ADDRESS_SPACE_OVERHEAD_BYTES: u64 = 4 << 30;
+
 impl Program {
-    fn execute_command(&self, command: &mut std::process::Command) -> Result<()> {
-        use process_control::{ChildExt, Control};
-        let child = command.spawn()?;
-        let output = child
-            .controlled_with_output()
-            .time_limit(std::time::Duration::from_secs_f64(self.time_limit_secs))
-            .memory_limit((self.memory_limit_mb * 1024.0 * 1024.0) as usize)
-            .terminate_for_timeout()
-            .wait()?
-            .ok_or_else(|| anyhow::anyhow!("time limit exceeded: {}", &self))?;
-        if !output.status.success() {
-            return Err(anyhow::anyhow!("runtime error: {}", &self));
+    /// Hash of the program and its files, used to detect changes between runs.
+    pub fn fingerprint(&self) -> Result<u64> {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = crate::utils::StableHasher::new();
+        self.to_string().hash(&mut hasher);
+        for path in self.info.files() {
+            if path.is_file() {
+                std::fs::read(path)?.hash(&mut hasher);
+            }
         }
-        Ok(())
+        Ok(hasher.finish())
+    }
+
+    fn execute_command(
+        &self,
+        command: &mut std::process::Command,
+        monitor: &process::Monitor,
+    ) -> Result<ExecuteResult> {
+        let start = std::time::Instant::now();
+        let child = command.stderr(std::process::Stdio::piped()).spawn()?;
+        Self::wait_child(child, monitor, start)
+    }
+
+    fn limits(&self) -> process::Limits {
+        let memory_bytes = (self.memory_limit_mb * 1024.0 * 1024.0) as u64;
+        process::Limits {
+            cpu_time: std::time::Duration::from_secs_f64(self.time_limit_secs),
+            wall_time: std::time::Duration::from_secs_f64(self.wall_time_limit_secs),
+            memory_bytes: Some(memory_bytes),
+            // the jvm reserves far more virtual memory than its heap, see `jvm_memory_args`
+            address_space_bytes: Some(if self.info.is_jvm() {
+                memory_bytes + JVM_ADDRESS_SPACE_OVERHEAD_BYTES
+            } else {
+                memory_bytes
+            }),
+            output_bytes: self
+                .output_limit_mb
+                .map(|output_limit_mb| (output_limit_mb * 1024.0 * 1024.0) as u64),
+            stack_bytes: (self.stack_limit_mb * 1024.0 * 1024.0) as u64,
+        }
+    }
+
+    fn wait_child(
+        mut child: std::process::Child,
+        monitor: &process::Monitor,
+        start: std::time::Instant,
+    ) -> Result<ExecuteResult> {
+        use std::io::Read;
+        // stderr is read concurrently, so that the child never blocks on a full pipe
+        let stderr = child.stderr.take();
+        let stderr_handle = std::thread::spawn(move || {
+            let mut buffer = vec![];
+            let mut truncated = false;
+            if let Some(mut stderr) = stderr {
+                let limit = ExecuteResult::STDERR_LIMIT_BYTES as u64;
+                let _ = (&mut stderr).take(limit).read_to_end(&mut buffer);
+                truncated = std::io::copy(&mut stderr, &mut std::io::sink()).unwrap_or(0) > 0;
+            }
+            let mut stderr = String::from_utf8_lossy(&buffer).to_string();
+            if truncated {
+                stderr.push_str("\n... (truncated)");
+            }
+            stderr
+        });
+        let (status, usage) = monitor.wait(&mut child, start)?;
+        let stderr = stderr_handle.join().unwrap();
+        Ok(ExecuteResult {
+            status,
+            stderr,
+            usage,
+        })
+    }
+
+    fn jvm_memory_args(&self) -> Vec<String> {
+        vec![
+            format!("-Xmx{}m", self.memory_limit_mb.ceil() as u64),
+            format!("-Xss{}m", self.stack_limit_mb.ceil() as u64),
+        ]
+    }
+
+    /// Paths are made absolute, so that the command can run in any directory.
+    /// Only `work_dir` is writable in the sandbox.
+    fn command(
+        &self,
+        args: &[String],
+        work_dir: Option<&std::path::Path>,
+    ) -> Result<(std::process::Command, process::Monitor)> {
+        use crate::utils::absolute_path;
+        let mut command = match &self.info {
+            ProgramInfo::Command(CommandProgram { path, extra_args }) => {
+                // a bare command name is searched in `PATH`
+                let mut command = if path.components().count() > 1 {
+                    std::process::Command::new(absolute_path(path))
+                } else {
+                    std::process::Command::new(path)
+                };
+                command.args(extra_args).args(args);
+                command
+            }
+            ProgramInfo::Cpp(CppProgram { path, .. })
+            | ProgramInfo::Rust(RustProgram { path, .. }) => {
+                let mut command = std::process::Command::new(absolute_path(path));
+                command.args(args);
+                command
+            }
+            ProgramInfo::Python(PythonProgram {
+                path,
+                interpreter,
+                interpreter_args,
+            }) => {
+                let mut command = std::process::Command::new(interpreter);
+                command
+                    .args(interpreter_args)
+                    .arg(absolute_path(path))
+                    .args(args);
+                command
+            }
+            ProgramInfo::Java(JavaProgram {
+                class_path,
+                main_class,
+                jvm_args,
+                ..
+            }) => {
+                let mut command = std::process::Command::new("java");
+                command
+                    .args(self.jvm_memory_args())
+                    .args(jvm_args)
+                    .arg("-cp")
+                    .arg(absolute_path(class_path))
+                    .arg(main_class)
+                    .args(args);
+                command
+            }
+            ProgramInfo::Kotlin(KotlinProgram {
+                jar_path, jvm_args, ..
+            }) => {
+                let mut command = std::process::Command::new("java");
+                command
+                    .args(self.jvm_memory_args())
+                    .args(jvm_args)
+                    .arg("-jar")
+                    .arg(absolute_path(jar_path))
+                    .args(args);
+                command
+            }
+            ProgramInfo::Custom(CustomProgram { command: run, .. }) => {
+                let mut command = std::process::Command::new(&run[0]);
+                command.args(&run[1..]).args(args);
+                command
+            }
+        };
+        if let Some(work_dir) = work_dir {
+            command.current_dir(work_dir);
+        }
+        // the process joins its cgroup before entering the sandbox
+        let monitor = process::Monitor::new(&mut command, self.limits());
+        if self.sandbox {
+            sandbox::apply(&mut command, work_dir)?;
+        }
+        Ok((command, monitor))
+    }
+
+    /// Runs the program, a time limit exceeded or a failed exit status is not an error.
+    pub fn run(
+        &self,
+        args: Vec<String>,
+        input: Option<std::fs::File>,
+        output: Option<std::fs::File>,
+    ) -> Result<ExecuteResult> {
+        self.run_in(args, None, input, output)
+    }
+
+    /// Same as [`Program::run`], in the working directory `work_dir` if given.
+    pub fn run_in(
+        &self,
+        args: Vec<String>,
+        work_dir: Option<&std::path::Path>,
+        input: Option<std::fs::File>,
+        output: Option<std::fs::File>,
+    ) -> Result<ExecuteResult> {
+        self.run_with_envs(args, work_dir, &[], input, output)
+    }
+
+    /// Same as [`Program::run_in`], with the environment variables `envs` set.
+    pub fn run_with_envs(
+        &self,
+        args: Vec<String>,
+        work_dir: Option<&std::path::Path>,
+        envs: &[(&str, String)],
+        input: Option<std::fs::File>,
+        output: Option<std::fs::File>,
+    ) -> Result<ExecuteResult> {
+        let (mut command, monitor) = self
+            .command(&args, work_dir)
+            .with_context(|| format!("failed to execute {} (args: `{}`)", self, args.join(" ")))?;
+        command.envs(envs.iter().map(|(key, value)| (key, value)));
+        if let Some(input) = input {
+            command.stdin(input);
+        }
+        if let Some(output) = output {
+            command.stdout(output);
+        }
+
+        self.execute_command(&mut command, &monitor)
+            .with_context(|| format!("failed to execute {} (args: `{}`)", self, args.join(" ")))
+    }
+
+    /// Runs the program with its stdin and stdout connected to the stdout and stdin of
+    /// the interactor, each under its own limits. Returns results of both programs.
+    pub fn run_interactive(
+        &self,
+        args: Vec<String>,
+        interactor: &Program,
+        interactor_args: Vec<String>,
+    ) -> Result<(ExecuteResult, ExecuteResult)> {
+        use std::process::Stdio;
+        let start = std::time::Instant::now();
+        let (mut interactor_child, interactor_monitor) = interactor
+            .command(&interactor_args, None)
+            .and_then(|(mut command, monitor)| {
+                let child = command
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()?;
+                Ok((child, monitor))
+            })
+            .with_context(|| format!("failed to execute interactor {}", interactor))?;
+        // the command is dropped right after spawning to close the pipes held by it,
+        // so that either program gets EOF once the other one exits
+        let solution_child = self
+            .command(&args, None)
+            .and_then(|(mut command, monitor)| {
+                let child = command
+                    .stdin(Stdio::from(interactor_child.stdout.take().unwrap()))
+                    .stdout(Stdio::from(interactor_child.stdin.take().unwrap()))
+                    .stderr(Stdio::piped())
+                    .spawn()?;
+                Ok((child, monitor))
+            });
+        let (solution_child, solution_monitor) = match solution_child {
+            Ok(solution_child) => solution_child,
+            Err(error) => {
+                let _ = interactor_child.kill();
+                let _ = interactor_child.wait();
+                return Err(error).with_context(|| {
+                    format!("failed to execute {} (args: `{}`)", self, args.join(" "))
+                });
+            }
+        };
+
+        let interactor_handle = std::thread::spawn(move || {
+            Self::wait_child(interactor_child, &interactor_monitor, start)
+        });
+        let solution_result = Self::wait_child(solution_child, &solution_monitor, start)
+            .with_context(|| format!("failed to execute {} (args: `{}`)", self, args.join(" ")));
+        let interactor_result = interactor_handle.join().unwrap();
+        Ok((solution_result?, interactor_result?))
     }
 
     pub fn execute(
@@ -89,32 +625,38 @@ impl Program {
         args: Vec<String>,
         input: Option<std::fs::File>,
         output: Option<std::fs::File>,
-    ) -> Result<()> {
-        match &self.info {
-            ProgramInfo::Command(CommandProgram { path, extra_args }) => {
-                let mut command = std::process::Command::new(path);
-                if let Some(input) = input {
-                    command.stdin(input);
-                }
-                if let Some(output) = output {
-                    command.stdout(output);
-                }
-                command.args(extra_args).args(args.clone());
-
-                self.execute_command(&mut command)
+    ) -> Result<ExecuteResult> {
+        self.execute_in(args, None, &[], input, output)
+    }
+
+    /// Same as [`Program::execute`], in the working directory `work_dir` if given,
+    /// with the environment variables `envs` set.
+    pub fn execute_in(
+        &self,
+        args: Vec<String>,
+        work_dir: Option<&std::path::Path>,
+        envs: &[(&str, String)],
+        input: Option<std::fs::File>,
+        output: Option<std::fs::File>,
+    ) -> Result<ExecuteResult> {
+        let result = self.run_with_envs(args.clone(), work_dir, envs, input, output)?;
+        match result.status {
+            ExecuteStatus::TimeLimitExceeded => {
+                Err(anyhow::anyhow!("time limit exceeded: {}", &self))
+            }
+            ExecuteStatus::OutputLimitExceeded => {
+                Err(anyhow::anyhow!("output limit exceeded: {}", &self))
             }
-            ProgramInfo::Cpp(CppProgram { path, .. }) => {
-                let mut command = std::process::Command::new(path);
-                if let Some(input) = input {
-                    command.stdin(input);
-                }
-                if let Some(output) = output {
-                    command.stdout(output);
-                }
-                command.args(args.clone());
-
-                self.execute_command(&mut command)
+            ExecuteStatus::MemoryLimitExceeded => {
+                Err(anyhow::anyhow!("memory limit exceeded: {}", &self))
             }
+            status if !status.success() => Err(anyhow::anyhow!(
+                "runtime error ({}): {}\n{}",
+                status,
+                &self,
+                result.stderr.trim_end()
+            )),
+            _ => return Ok(result),
         }
         .with_context(|| format!("failed to execute {} (args: `{}`)", self, args.join(" ")))
     }
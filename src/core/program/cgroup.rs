@@ -0,0 +1,234 @@
+//! Per-run cgroups (cgroups v2, linux only), which account for every process of a run.
+//!
+//! Cgroups are created under `$CPTOOL_CGROUP` if set, or under the cgroup of cptool,
+//! which must be able to enable the memory and pids controllers for its children,
+//! e.g. a delegated cgroup created by `systemd-run --user --scope -p Delegate=yes`.
+//! As a cgroup with processes can not enable controllers for its children, cptool moves itself
+//! into the leaf child `cptool` of its cgroup, next to the per-run cgroups.
+//!
+//! Without cgroups, limits fall back to rlimits, which is reported once on stderr.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Maximum number of processes and threads of a run.
+pub const PIDS_MAX: u64 = 256;
+
+pub struct Cgroup {
+    path: PathBuf,
+    procs: std::fs::File,
+}
+
+impl Cgroup {
+    /// Creates a cgroup for a single run, `None` if cgroups v2 is unavailable.
+    pub fn create(memory_bytes: Option<u64>) -> Option<Self> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = base_dir()?.join(format!(
+            "cptool-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        if let Err(error) = std::fs::create_dir(&path) {
+            warn_fallback(&format!("failed to create {}: {}", path.display(), error));
+            return None;
+        }
+        let procs = std::fs::OpenOptions::new()
+            .write(true)
+            .open(path.join("cgroup.procs"));
+        let procs = match procs {
+            Ok(procs) => procs,
+            Err(error) => {
+                let _ = std::fs::remove_dir(&path);
+                warn_fallback(&format!(
+                    "failed to open cgroup.procs of {}: {}",
+                    path.display(),
+                    error
+                ));
+                return None;
+            }
+        };
+        // the cgroup is removed on drop if a limit can not be set
+        let cgroup = Self { path, procs };
+        match cgroup.set_limits(memory_bytes) {
+            Ok(()) => Some(cgroup),
+            Err(error) => {
+                warn_fallback(&format!(
+                    "failed to set the limits of {}: {}",
+                    cgroup.path.display(),
+                    error
+                ));
+                None
+            }
+        }
+    }
+
+    fn set_limits(&self, memory_bytes: Option<u64>) -> std::io::Result<()> {
+        if let Some(memory_bytes) = memory_bytes {
+            self.write("memory.max", &memory_bytes.to_string())?;
+            // swap is not available if the kernel has no swap accounting
+            let _ = self.write("memory.swap.max", "0");
+        }
+        self.write("pids.max", &PIDS_MAX.to_string())
+    }
+
+    /// File descriptor of `cgroup.procs`, writing `0` to it moves the writing process into the cgroup.
+    pub fn procs_fd(&self) -> std::os::unix::io::RawFd {
+        use std::os::unix::io::AsRawFd;
+        self.procs.as_raw_fd()
+    }
+
+    /// Kills every process in the cgroup.
+    pub fn kill(&self) {
+        if self.write("cgroup.kill", "1").is_ok() {
+            return;
+        }
+        // `cgroup.kill` is available since linux 5.14
+        if let Ok(procs) = std::fs::read_to_string(self.path.join("cgroup.procs")) {
+            for pid in procs.lines().filter_map(|pid| pid.parse().ok()) {
+                unsafe { libc::kill(pid, libc::SIGKILL) };
+            }
+        }
+    }
+
+    /// Whether a process was killed for exceeding `memory.max`.
+    pub fn oom_killed(&self) -> bool {
+        self.read_key("memory.events", "oom_kill")
+            .is_some_and(|count| count > 0)
+    }
+
+    /// Peak memory usage of the cgroup, available since linux 5.19.
+    pub fn memory_peak(&self) -> Option<u64> {
+        std::fs::read_to_string(self.path.join("memory.peak"))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// CPU time of every process in the cgroup.
+    pub fn cpu_time(&self) -> Option<Duration> {
+        self.read_key("cpu.stat", "usage_usec")
+            .map(Duration::from_micros)
+    }
+
+    fn write(&self, file: &str, content: &str) -> std::io::Result<()> {
+        std::fs::write(self.path.join(file), content)
+    }
+
+    /// Reads a value from a flat keyed file like `memory.events`.
+    fn read_key(&self, file: &str, key: &str) -> Option<u64> {
+        std::fs::read_to_string(self.path.join(file))
+            .ok()?
+            .lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix(' ')?.parse().ok())
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        self.kill();
+        // the cgroup can only be removed once the killed processes are gone
+        for _ in 0..100 {
+            if std::fs::remove_dir(&self.path).is_ok() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+/// Cgroup in which the per-run cgroups are created, `None` if unavailable.
+fn base_dir() -> Option<&'static Path> {
+    static BASE_DIR: std::sync::OnceLock<Option<PathBuf>> = std::sync::OnceLock::new();
+    BASE_DIR
+        .get_or_init(|| match prepare_base_dir() {
+            Ok(base_dir) => Some(base_dir),
+            Err(reason) => {
+                warn_fallback(&reason);
+                None
+            }
+        })
+        .as_deref()
+}
+
+/// Enables the memory and pids controllers for the children of the base cgroup,
+/// moving cptool out of its own cgroup if needed.
+fn prepare_base_dir() -> Result<PathBuf, String> {
+    if let Some(dir) = std::env::var_os("CPTOOL_CGROUP") {
+        let base_dir = PathBuf::from(dir);
+        enable_controllers(&base_dir)?;
+        return Ok(base_dir);
+    }
+    let base_dir = own_cgroup().ok_or("cgroups v2 is not mounted")?;
+    check_controllers(&base_dir)?;
+    if enable_controllers(&base_dir).is_err() {
+        // fails if there are processes in the cgroup itself, which is cptool at least
+        let leaf = base_dir.join("cptool");
+        match std::fs::create_dir(&leaf) {
+            Ok(()) => {}
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(error) => return Err(format!("failed to create {}: {}", leaf.display(), error)),
+        }
+        std::fs::write(leaf.join("cgroup.procs"), std::process::id().to_string())
+            .map_err(|error| format!("failed to move cptool into {}: {}", leaf.display(), error))?;
+        enable_controllers(&base_dir)?;
+    }
+    Ok(base_dir)
+}
+
+/// Checks that the memory and pids controllers are available to the cgroup.
+fn check_controllers(dir: &Path) -> Result<(), String> {
+    let path = dir.join("cgroup.controllers");
+    let controllers = std::fs::read_to_string(&path)
+        .map_err(|error| format!("failed to read {}: {}", path.display(), error))?;
+    let available = |controller| controllers.split_whitespace().any(|c| c == controller);
+    if !(available("memory") && available("pids")) {
+        return Err(format!(
+            "the memory and pids controllers are not available in {}",
+            dir.display()
+        ));
+    }
+    Ok(())
+}
+
+fn enable_controllers(dir: &Path) -> Result<(), String> {
+    let path = dir.join("cgroup.subtree_control");
+    let controllers = std::fs::read_to_string(&path)
+        .map_err(|error| format!("failed to read {}: {}", path.display(), error))?;
+    let enabled = |controller| controllers.split_whitespace().any(|c| c == controller);
+    if !(enabled("memory") && enabled("pids")) {
+        std::fs::write(&path, "+memory +pids").map_err(|error| {
+            format!(
+                "failed to enable the memory and pids controllers in {}: {}",
+                path.display(),
+                error
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Reports once that runs are not put in cgroups.
+fn warn_fallback(reason: &str) {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    WARNED.call_once(|| {
+        eprintln!(
+            "warning: cgroups are unavailable ({}), falling back to rlimits, \
+             so memory is limited by address space and child processes are not accounted",
+            reason
+        );
+    });
+}
+
+/// Directory of the cgroup (v2) of the current process.
+fn own_cgroup() -> Option<PathBuf> {
+    let mount_info = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    let mount_point = mount_info.lines().find_map(|line| {
+        let (fields, fs) = line.split_once(" - ")?;
+        (fs.split(' ').next() == Some("cgroup2")).then(|| fields.split(' ').nth(4))?
+    })?;
+    let cgroups = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    let path = cgroups.lines().find_map(|line| line.strip_prefix("0::"))?;
+    Some(Path::new(mount_point).join(path.trim_start_matches('/')))
+}
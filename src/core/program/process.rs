@@ -0,0 +1,255 @@
+use super::{ExecuteStatus, ResourceUsage};
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "linux")]
+use super::cgroup::Cgroup;
+
+/// Limits applied to a single process.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub cpu_time: Duration,
+    pub wall_time: Duration,
+    pub memory_bytes: Option<u64>,
+    /// `RLIMIT_AS` if cgroups are unavailable, larger than `memory_bytes` for programs
+    /// reserving far more virtual memory than they use.
+    pub address_space_bytes: Option<u64>,
+    /// Maximum size of written files.
+    pub output_bytes: Option<u64>,
+    pub stack_bytes: u64,
+}
+
+/// Applies the limits to a command and waits for its process.
+///
+/// On linux, every run is put in its own cgroup if cgroups v2 is available,
+/// which limits and accounts for the memory of all processes of the run;
+/// otherwise the memory of the process is limited by `RLIMIT_AS`.
+pub struct Monitor {
+    limits: Limits,
+    #[cfg(target_os = "linux")]
+    cgroup: Option<Cgroup>,
+}
+
+impl Monitor {
+    /// Applies the limits that have to be set before the process starts.
+    #[cfg(unix)]
+    pub fn new(command: &mut std::process::Command, limits: Limits) -> Self {
+        use std::os::unix::process::CommandExt;
+        #[cfg(target_os = "linux")]
+        let cgroup = Cgroup::create(limits.memory_bytes);
+        #[cfg(target_os = "linux")]
+        let (procs_fd, memory_bytes) = match &cgroup {
+            Some(cgroup) => (Some(cgroup.procs_fd()), None),
+            None => (None, limits.address_space_bytes),
+        };
+        #[cfg(not(target_os = "linux"))]
+        let (procs_fd, memory_bytes): (Option<libc::c_int>, _) = (None, limits.address_space_bytes);
+        // the cpu time limit has a granularity of seconds and only stops runaway processes,
+        // the exact limit is checked against the resource usage after the process exits
+        let cpu_secs = limits.cpu_time.as_secs_f64().ceil().max(1.0) as u64;
+        let output_bytes = limits.output_bytes;
+        let stack_bytes = limits.stack_bytes;
+        // SAFETY: only async-signal-safe functions are called and nothing is allocated in the closure
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(procs_fd) = procs_fd {
+                    if libc::write(procs_fd, b"0".as_ptr() as *const libc::c_void, 1) != 1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                set_rlimit(libc::RLIMIT_CPU, cpu_secs, cpu_secs + 1)?;
+                if let Some(memory_bytes) = memory_bytes {
+                    set_rlimit(libc::RLIMIT_AS, memory_bytes, memory_bytes)?;
+                }
+                if let Some(output_bytes) = output_bytes {
+                    set_rlimit(libc::RLIMIT_FSIZE, output_bytes, output_bytes)?;
+                }
+                set_rlimit(libc::RLIMIT_STACK, stack_bytes, stack_bytes)?;
+                Ok(())
+            });
+        }
+        Self {
+            limits,
+            #[cfg(target_os = "linux")]
+            cgroup,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn new(_command: &mut std::process::Command, limits: Limits) -> Self {
+        Self { limits }
+    }
+
+    /// Waits for the child with `wait4`, killing it once the wall time limit is exceeded,
+    /// and returns its status with the resource usage reported by the kernel.
+    ///
+    /// The time limit is exceeded if either the cpu time or the wall time exceeds its limit.
+    #[cfg(unix)]
+    pub fn wait(
+        &self,
+        child: &mut std::process::Child,
+        start: Instant,
+    ) -> Result<(ExecuteStatus, ResourceUsage)> {
+        let pid = child.id() as libc::pid_t;
+        let mut timed_out = false;
+        let mut delay = Duration::from_millis(1);
+        loop {
+            let mut status = 0;
+            let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+            let options = if timed_out { 0 } else { libc::WNOHANG };
+            let ret = unsafe { libc::wait4(pid, &mut status, options, &mut rusage) };
+            if ret == -1 {
+                let error = std::io::Error::last_os_error();
+                if error.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(error.into());
+            }
+            if ret == pid {
+                #[allow(unused_mut)]
+                let mut usage = ResourceUsage {
+                    cpu_time: timeval_duration(rusage.ru_utime) + timeval_duration(rusage.ru_stime),
+                    wall_time: start.elapsed(),
+                    memory_bytes: max_rss_bytes(rusage.ru_maxrss),
+                };
+                // the peak memory only exceeds the limit if the address space limit is larger
+                #[allow(unused_mut)]
+                let mut memory_limit_exceeded = self
+                    .limits
+                    .memory_bytes
+                    .is_some_and(|memory_bytes| usage.memory_bytes > memory_bytes);
+                #[cfg(target_os = "linux")]
+                if let Some(cgroup) = &self.cgroup {
+                    // processes left behind are killed as well
+                    cgroup.kill();
+                    usage.cpu_time = cgroup.cpu_time().unwrap_or(usage.cpu_time);
+                    usage.memory_bytes = cgroup.memory_peak().unwrap_or(usage.memory_bytes);
+                    memory_limit_exceeded |= cgroup.oom_killed();
+                }
+                let status = if timed_out
+                    || usage.cpu_time > self.limits.cpu_time
+                    || (libc::WIFSIGNALED(status) && libc::WTERMSIG(status) == libc::SIGXCPU)
+                {
+                    ExecuteStatus::TimeLimitExceeded
+                } else if memory_limit_exceeded {
+                    ExecuteStatus::MemoryLimitExceeded
+                } else if libc::WIFSIGNALED(status) && libc::WTERMSIG(status) == libc::SIGXFSZ {
+                    ExecuteStatus::OutputLimitExceeded
+                } else if libc::WIFSIGNALED(status) {
+                    ExecuteStatus::Signaled(libc::WTERMSIG(status))
+                } else {
+                    ExecuteStatus::Exited(libc::WEXITSTATUS(status))
+                };
+                return Ok((status, usage));
+            }
+            // the child is not reaped yet, so the pid can not be reused by another process
+            if start.elapsed() >= self.limits.wall_time {
+                unsafe { libc::kill(pid, libc::SIGKILL) };
+                #[cfg(target_os = "linux")]
+                if let Some(cgroup) = &self.cgroup {
+                    cgroup.kill();
+                }
+                timed_out = true;
+                continue;
+            }
+            std::thread::sleep(delay);
+            delay = (delay * 2).min(Duration::from_millis(10));
+        }
+    }
+
+    /// Fallback without resource usage, where only the wall time is measured and limited.
+    #[cfg(not(unix))]
+    pub fn wait(
+        &self,
+        child: &mut std::process::Child,
+        start: Instant,
+    ) -> Result<(ExecuteStatus, ResourceUsage)> {
+        use process_control::{ChildExt, Control};
+        let control = child
+            .controlled()
+            .time_limit(self.limits.wall_time)
+            .terminate_for_timeout();
+        let control = match self.limits.memory_bytes {
+            Some(memory_bytes) => control.memory_limit(memory_bytes as usize),
+            None => control,
+        };
+        let status = match control.wait()? {
+            Some(status) => ExecuteStatus::Exited(status.code().unwrap_or(-1) as i32),
+            None => ExecuteStatus::TimeLimitExceeded,
+        };
+        let wall_time = start.elapsed();
+        Ok((
+            status,
+            ResourceUsage {
+                cpu_time: wall_time,
+                wall_time,
+                memory_bytes: 0,
+            },
+        ))
+    }
+}
+
+/// Name of common signals that terminate programs.
+#[cfg(unix)]
+pub fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        libc::SIGSYS => "SIGSYS",
+        _ => return None,
+    })
+}
+
+#[cfg(not(unix))]
+pub fn signal_name(_signal: i32) -> Option<&'static str> {
+    None
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type Resource = libc::c_int;
+
+/// Limits are lowered to the current hard limit, which can not be raised without privileges.
+#[cfg(unix)]
+fn set_rlimit(resource: Resource, soft: u64, hard: u64) -> std::io::Result<()> {
+    let mut current: libc::rlimit = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrlimit(resource, &mut current) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let hard = (hard as libc::rlim_t).min(current.rlim_max);
+    let limit = libc::rlimit {
+        rlim_cur: (soft as libc::rlim_t).min(hard),
+        rlim_max: hard,
+    };
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn timeval_duration(time: libc::timeval) -> Duration {
+    Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+}
+
+/// `ru_maxrss` is in kilobytes on linux, but in bytes on macos.
+#[cfg(unix)]
+fn max_rss_bytes(max_rss: libc::c_long) -> u64 {
+    if cfg!(target_os = "macos") {
+        max_rss as u64
+    } else {
+        max_rss as u64 * 1024
+    }
+}
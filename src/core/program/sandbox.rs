@@ -0,0 +1,897 @@
+//! Sandbox for untrusted programs, linux only.
+//!
+//! The program runs in fresh user, mount, network, pid, ipc and uts namespaces,
+//! where every mount is read-only except the working directory and a private scratch directory,
+//! there is no network, and only an allowlist of system calls is permitted by a seccomp filter.
+//! The problem directory, the current directory of cptool, is hidden under an empty tmpfs,
+//! except the files named by the command and the working directory, so that answers, checkers
+//! and other solutions can not be read.
+//! The program can create threads but no other processes.
+
+use anyhow::Result;
+
+/// Runs the command in the sandbox, where only `writable_dir` and a private scratch directory,
+/// given as `$TMPDIR`, can be written to.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn apply(
+    command: &mut std::process::Command,
+    writable_dir: Option<&std::path::Path>,
+) -> Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::process::CommandExt;
+    // the program, its script or class path, and arguments like input files stay visible
+    let base_dir = command.get_current_dir().map(crate::utils::absolute_path);
+    let exposed_paths = std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| match &base_dir {
+            Some(base_dir) => base_dir.join(arg),
+            None => crate::utils::absolute_path(std::path::Path::new(arg)),
+        })
+        .filter(|path| path.exists())
+        .collect();
+    let sandbox = Sandbox::new(writable_dir, exposed_paths)?;
+    command.env(
+        "TMPDIR",
+        std::ffi::OsStr::from_bytes(sandbox.scratch_dir().to_bytes()),
+    );
+    // SAFETY: only async-signal-safe functions are called in the closure,
+    // everything allocated is prepared in `Sandbox::new`
+    unsafe {
+        command.pre_exec(move || sandbox.enter());
+    }
+    Ok(())
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn apply(
+    _command: &mut std::process::Command,
+    _writable_dir: Option<&std::path::Path>,
+) -> Result<()> {
+    Err(anyhow::anyhow!(
+        "sandbox is only supported on linux (x86_64 and aarch64)"
+    ))
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+use linux::Sandbox;
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod linux {
+    use anyhow::{Context, Result};
+    use std::ffi::CString;
+    use std::io::Error;
+
+    /// User and group id of the program in the sandbox, so that it gets no capabilities.
+    const SANDBOX_ID: u32 = 1000;
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+    #[cfg(target_arch = "x86_64")]
+    const ARCH_SYSCALLS: &[libc::c_long] = &[
+        libc::SYS_open,
+        libc::SYS_stat,
+        libc::SYS_lstat,
+        libc::SYS_access,
+        libc::SYS_readlink,
+        libc::SYS_pipe,
+        libc::SYS_dup2,
+        libc::SYS_poll,
+        libc::SYS_select,
+        libc::SYS_epoll_wait,
+        libc::SYS_arch_prctl,
+        libc::SYS_time,
+        libc::SYS_unlink,
+        libc::SYS_mkdir,
+        libc::SYS_rmdir,
+        libc::SYS_rename,
+        libc::SYS_alarm,
+        libc::SYS_getpgrp,
+        libc::SYS_getdents,
+    ];
+    #[cfg(target_arch = "aarch64")]
+    const ARCH_SYSCALLS: &[libc::c_long] = &[];
+
+    /// System calls needed by common runtimes (c, c++, python, jvm).
+    const SYSCALLS: &[libc::c_long] = &[
+        // files
+        libc::SYS_read,
+        libc::SYS_write,
+        libc::SYS_readv,
+        libc::SYS_writev,
+        libc::SYS_pread64,
+        libc::SYS_pwrite64,
+        libc::SYS_lseek,
+        libc::SYS_openat,
+        libc::SYS_close,
+        libc::SYS_close_range,
+        libc::SYS_fstat,
+        libc::SYS_newfstatat,
+        libc::SYS_statx,
+        libc::SYS_statfs,
+        libc::SYS_fstatfs,
+        libc::SYS_faccessat,
+        libc::SYS_faccessat2,
+        libc::SYS_readlinkat,
+        libc::SYS_getdents64,
+        libc::SYS_getcwd,
+        libc::SYS_chdir,
+        libc::SYS_fchdir,
+        libc::SYS_fcntl,
+        libc::SYS_flock,
+        libc::SYS_dup,
+        libc::SYS_dup3,
+        libc::SYS_pipe2,
+        libc::SYS_ftruncate,
+        libc::SYS_fsync,
+        libc::SYS_fdatasync,
+        libc::SYS_unlinkat,
+        libc::SYS_mkdirat,
+        libc::SYS_renameat,
+        libc::SYS_renameat2,
+        libc::SYS_umask,
+        libc::SYS_ppoll,
+        libc::SYS_pselect6,
+        libc::SYS_epoll_create1,
+        libc::SYS_epoll_ctl,
+        libc::SYS_epoll_pwait,
+        libc::SYS_eventfd2,
+        // memory
+        libc::SYS_brk,
+        libc::SYS_mmap,
+        libc::SYS_munmap,
+        libc::SYS_mremap,
+        libc::SYS_mprotect,
+        libc::SYS_madvise,
+        libc::SYS_mincore,
+        libc::SYS_memfd_create,
+        libc::SYS_membarrier,
+        // processes and threads
+        libc::SYS_execve,
+        libc::SYS_execveat,
+        libc::SYS_exit,
+        libc::SYS_exit_group,
+        libc::SYS_wait4,
+        libc::SYS_waitid,
+        libc::SYS_kill,
+        libc::SYS_tkill,
+        libc::SYS_tgkill,
+        libc::SYS_getpid,
+        libc::SYS_getppid,
+        libc::SYS_gettid,
+        libc::SYS_getpgid,
+        libc::SYS_getuid,
+        libc::SYS_geteuid,
+        libc::SYS_getgid,
+        libc::SYS_getegid,
+        libc::SYS_getresuid,
+        libc::SYS_getresgid,
+        libc::SYS_getgroups,
+        libc::SYS_set_tid_address,
+        libc::SYS_set_robust_list,
+        libc::SYS_get_robust_list,
+        libc::SYS_rseq,
+        libc::SYS_futex,
+        libc::SYS_futex_waitv,
+        libc::SYS_prlimit64,
+        libc::SYS_getrlimit,
+        libc::SYS_getrusage,
+        libc::SYS_sched_yield,
+        libc::SYS_sched_getaffinity,
+        libc::SYS_sched_getparam,
+        libc::SYS_sched_getscheduler,
+        libc::SYS_getcpu,
+        // signals
+        libc::SYS_rt_sigaction,
+        libc::SYS_rt_sigprocmask,
+        libc::SYS_rt_sigreturn,
+        libc::SYS_rt_sigsuspend,
+        libc::SYS_rt_sigtimedwait,
+        libc::SYS_sigaltstack,
+        // time and system information
+        libc::SYS_nanosleep,
+        libc::SYS_clock_nanosleep,
+        libc::SYS_clock_gettime,
+        libc::SYS_clock_getres,
+        libc::SYS_gettimeofday,
+        libc::SYS_getitimer,
+        libc::SYS_setitimer,
+        libc::SYS_times,
+        libc::SYS_uname,
+        libc::SYS_sysinfo,
+        libc::SYS_getrandom,
+    ];
+
+    /// Namespaces that can not be created in the sandbox, `CLONE_NEWTIME` is missing in libc.
+    const CLONE_NEW_FLAGS: libc::c_int = libc::CLONE_NEWUSER
+        | libc::CLONE_NEWNS
+        | libc::CLONE_NEWNET
+        | libc::CLONE_NEWPID
+        | libc::CLONE_NEWIPC
+        | libc::CLONE_NEWUTS
+        | libc::CLONE_NEWCGROUP
+        | 0x80;
+
+    /// `prctl` options used by runtimes to name threads and memory mappings.
+    const PRCTL_OPTIONS: &[libc::c_int] = &[
+        libc::PR_SET_NAME,
+        libc::PR_GET_NAME,
+        libc::PR_GET_DUMPABLE,
+        // `PR_SET_VMA`, missing in libc
+        0x5356_4d41,
+    ];
+
+    /// `ioctl` requests used by runtimes to inspect their standard streams.
+    const IOCTL_REQUESTS: &[libc::Ioctl] = &[
+        libc::TCGETS,
+        libc::TIOCGWINSZ,
+        libc::FIONREAD,
+        libc::FIONBIO,
+        libc::FIOCLEX,
+        libc::FIONCLEX,
+    ];
+
+    /// `open_tree` and `move_mount`, missing in libc, have the same number on every architecture.
+    const SYS_OPEN_TREE: libc::c_long = 428;
+    const SYS_MOVE_MOUNT: libc::c_long = 429;
+    /// `OPEN_TREE_CLONE`, missing in libc.
+    const OPEN_TREE_CLONE: libc::c_uint = 1;
+    /// `MOVE_MOUNT_F_EMPTY_PATH`, missing in libc for musl.
+    const MOVE_MOUNT_F_EMPTY_PATH: libc::c_uint = 4;
+
+    /// Name of the scratch directory, created in the tmpfs hiding the problem directory.
+    const SCRATCH_NAME: &str = ".scratch";
+
+    /// Options of the tmpfs mounted on the scratch directory, whose pages are also charged
+    /// to the cgroup of the run.
+    const SCRATCH_OPTIONS: &std::ffi::CStr = c"size=256m,mode=1777";
+
+    /// Everything the sandboxed child needs, prepared before forking.
+    pub struct Sandbox {
+        writable_dir: Option<CString>,
+        hidden_dir: CString,
+        exposed: Vec<Exposed>,
+        scratch_dir: CString,
+        mount_points: Vec<CString>,
+        uid_map: Vec<u8>,
+        gid_map: Vec<u8>,
+        filter: Vec<libc::sock_filter>,
+    }
+
+    /// A path in the hidden directory, mounted back onto an empty file or directory of the tmpfs.
+    struct Exposed {
+        /// Relative to the hidden directory.
+        source: CString,
+        target: CString,
+        /// Directories to create for the target, outermost first.
+        parents: Vec<CString>,
+        is_dir: bool,
+    }
+
+    impl Sandbox {
+        pub fn new(
+            writable_dir: Option<&std::path::Path>,
+            exposed_paths: Vec<std::path::PathBuf>,
+        ) -> Result<Self> {
+            use std::os::unix::ffi::OsStrExt;
+            let c_path = |path: &std::path::Path| CString::new(path.as_os_str().as_bytes());
+            let hidden_dir = crate::utils::absolute_path(&std::env::current_dir()?);
+            if hidden_dir.parent().is_none() {
+                return Err(anyhow::anyhow!(
+                    "the sandbox can not hide the root directory, run in a problem directory"
+                ));
+            }
+            let writable_path = writable_dir.map(crate::utils::absolute_path);
+            if writable_path
+                .as_ref()
+                .is_some_and(|dir| hidden_dir.starts_with(dir))
+            {
+                return Err(anyhow::anyhow!(
+                    "the sandbox can not hide {}, which is in the writable directory",
+                    hidden_dir.display()
+                ));
+            }
+
+            let mut exposed_paths: Vec<_> = exposed_paths
+                .into_iter()
+                .chain(writable_path.clone())
+                .filter(|path| path.starts_with(&hidden_dir) && path != &hidden_dir)
+                .collect();
+            exposed_paths.sort();
+            // a path in an exposed directory is exposed with it
+            exposed_paths.dedup_by(|path, ancestor| path.starts_with(ancestor));
+            let exposed = exposed_paths
+                .iter()
+                .map(|path| {
+                    let source = path.strip_prefix(&hidden_dir)?;
+                    let parents = source
+                        .ancestors()
+                        .skip(1)
+                        .filter(|parent| !parent.as_os_str().is_empty())
+                        .map(|parent| c_path(&hidden_dir.join(parent)))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Exposed {
+                        source: c_path(source)?,
+                        target: c_path(path)?,
+                        parents: parents.into_iter().rev().collect(),
+                        is_dir: path.is_dir(),
+                    })
+                })
+                .collect::<Result<_>>()?;
+
+            let writable_dir = writable_path.as_deref().map(c_path).transpose()?;
+            let mount_points = mount_points()?
+                .into_iter()
+                .filter(|mount_point| Some(mount_point) != writable_dir.as_ref())
+                .collect();
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            Ok(Self {
+                writable_dir,
+                scratch_dir: c_path(&hidden_dir.join(SCRATCH_NAME))?,
+                hidden_dir: c_path(&hidden_dir)?,
+                exposed,
+                mount_points,
+                uid_map: format!("{} {} 1", SANDBOX_ID, uid).into_bytes(),
+                gid_map: format!("{} {} 1", SANDBOX_ID, gid).into_bytes(),
+                filter: seccomp_filter(),
+            })
+        }
+
+        pub fn scratch_dir(&self) -> &std::ffi::CStr {
+            &self.scratch_dir
+        }
+
+        /// Enters the sandbox in the child process right before `exec`.
+        pub fn enter(&self) -> std::io::Result<()> {
+            unsafe {
+                check(libc::unshare(
+                    libc::CLONE_NEWUSER
+                        | libc::CLONE_NEWNS
+                        | libc::CLONE_NEWNET
+                        | libc::CLONE_NEWPID
+                        | libc::CLONE_NEWIPC
+                        | libc::CLONE_NEWUTS,
+                ))?;
+                write_file(c"/proc/self/setgroups", b"deny")?;
+                write_file(c"/proc/self/uid_map", &self.uid_map)?;
+                write_file(c"/proc/self/gid_map", &self.gid_map)?;
+
+                check(libc::mount(
+                    std::ptr::null(),
+                    c"/".as_ptr(),
+                    std::ptr::null(),
+                    libc::MS_REC | libc::MS_PRIVATE,
+                    std::ptr::null(),
+                ))?;
+                if let Some(dir) = &self.writable_dir {
+                    // a bind mount of its own is not affected by remounting its parent
+                    check(libc::mount(
+                        dir.as_ptr(),
+                        dir.as_ptr(),
+                        std::ptr::null(),
+                        libc::MS_BIND | libc::MS_REC,
+                        std::ptr::null(),
+                    ))?;
+                }
+                for mount_point in self.mount_points.iter() {
+                    remount_read_only(mount_point)?;
+                }
+                self.hide_problem_dir()?;
+                // the working directory still refers to the mounts below
+                let work_dir = self.writable_dir.as_ref().unwrap_or(&self.hidden_dir);
+                check(libc::chdir(work_dir.as_ptr()))?;
+
+                // the program has to be a child to run in the new pid namespace, but not its
+                // init process, which ignores signals like `SIGXCPU`; so an init process in
+                // between reports the wait status of the program to this process through a pipe,
+                // then this process exits the same way
+                let mut status_pipe = [0; 2];
+                check(libc::pipe2(status_pipe.as_mut_ptr(), libc::O_CLOEXEC))?;
+                libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+                let pid = check_fork()?;
+                if pid > 0 {
+                    libc::close(status_pipe[1]);
+                    wait_and_exit(pid, status_pipe[0]);
+                }
+                libc::close(status_pipe[0]);
+                libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+                // a fresh procfs for the new pid namespace, the old one is kept if not permitted
+                libc::mount(
+                    c"proc".as_ptr(),
+                    c"/proc".as_ptr(),
+                    c"proc".as_ptr(),
+                    libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                    std::ptr::null(),
+                );
+                let pid = check_fork()?;
+                if pid > 0 {
+                    report_and_exit(pid, status_pipe[1]);
+                }
+                libc::close(status_pipe[1]);
+                libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+
+                check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+                let program = libc::sock_fprog {
+                    len: self.filter.len() as libc::c_ushort,
+                    filter: self.filter.as_ptr() as *mut libc::sock_filter,
+                };
+                check(libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    libc::SECCOMP_MODE_FILTER,
+                    &program as *const libc::sock_fprog,
+                ))?;
+            }
+            Ok(())
+        }
+
+        /// Mounts an empty tmpfs on the hidden directory, then the exposed paths, cloned from
+        /// below the tmpfs with their read-only flags, and the scratch directory onto it.
+        unsafe fn hide_problem_dir(&self) -> std::io::Result<()> {
+            let hidden_fd = libc::open(
+                self.hidden_dir.as_ptr(),
+                libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC,
+            );
+            check(hidden_fd)?;
+            let result = self.mount_hiding_tmpfs(hidden_fd);
+            libc::close(hidden_fd);
+            result
+        }
+
+        unsafe fn mount_hiding_tmpfs(&self, hidden_fd: libc::c_int) -> std::io::Result<()> {
+            check(libc::mount(
+                c"tmpfs".as_ptr(),
+                self.hidden_dir.as_ptr(),
+                c"tmpfs".as_ptr(),
+                libc::MS_NOSUID | libc::MS_NODEV,
+                c"mode=755".as_ptr() as *const libc::c_void,
+            ))?;
+            for exposed in self.exposed.iter() {
+                for parent in exposed.parents.iter() {
+                    make_dir(parent)?;
+                }
+                if exposed.is_dir {
+                    make_dir(&exposed.target)?;
+                } else {
+                    let fd = libc::open(
+                        exposed.target.as_ptr(),
+                        libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC,
+                        0o644,
+                    );
+                    check(fd)?;
+                    libc::close(fd);
+                }
+                let tree_fd = libc::syscall(
+                    SYS_OPEN_TREE,
+                    hidden_fd,
+                    exposed.source.as_ptr(),
+                    OPEN_TREE_CLONE
+                        | libc::O_CLOEXEC as libc::c_uint
+                        | libc::AT_RECURSIVE as libc::c_uint,
+                ) as libc::c_int;
+                check(tree_fd)?;
+                let moved = libc::syscall(
+                    SYS_MOVE_MOUNT,
+                    tree_fd,
+                    c"".as_ptr(),
+                    libc::AT_FDCWD,
+                    exposed.target.as_ptr(),
+                    MOVE_MOUNT_F_EMPTY_PATH,
+                ) as libc::c_int;
+                libc::close(tree_fd);
+                check(moved)?;
+            }
+            make_dir(&self.scratch_dir)?;
+            check(libc::mount(
+                c"tmpfs".as_ptr(),
+                self.scratch_dir.as_ptr(),
+                c"tmpfs".as_ptr(),
+                libc::MS_NOSUID | libc::MS_NODEV,
+                SCRATCH_OPTIONS.as_ptr() as *const libc::c_void,
+            ))?;
+            remount_read_only(&self.hidden_dir)
+        }
+    }
+
+    fn check(ret: libc::c_int) -> std::io::Result<()> {
+        if ret < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    unsafe fn write_file(path: &std::ffi::CStr, content: &[u8]) -> std::io::Result<()> {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        check(fd)?;
+        let written = libc::write(fd, content.as_ptr() as *const libc::c_void, content.len());
+        libc::close(fd);
+        if written != content.len() as isize {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Creates a directory, which may already exist.
+    unsafe fn make_dir(path: &CString) -> std::io::Result<()> {
+        if libc::mkdir(path.as_ptr(), 0o755) < 0 && *libc::__errno_location() != libc::EEXIST {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Flags that can not be cleared in a user namespace have to be kept when remounting.
+    unsafe fn remount_read_only(mount_point: &CString) -> std::io::Result<()> {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(mount_point.as_ptr(), &mut stat) < 0 {
+            // mount points that can not be accessed can not be written to either
+            return Ok(());
+        }
+        let mut flags = libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY;
+        for (st_flag, ms_flag) in [
+            (libc::ST_NOSUID, libc::MS_NOSUID),
+            (libc::ST_NODEV, libc::MS_NODEV),
+            (libc::ST_NOEXEC, libc::MS_NOEXEC),
+            (libc::ST_NOATIME, libc::MS_NOATIME),
+            (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+            (libc::ST_RELATIME, libc::MS_RELATIME),
+        ] {
+            if stat.f_flag & st_flag != 0 {
+                flags |= ms_flag;
+            }
+        }
+        check(libc::mount(
+            std::ptr::null(),
+            mount_point.as_ptr(),
+            std::ptr::null(),
+            flags,
+            std::ptr::null(),
+        ))
+    }
+
+    unsafe fn check_fork() -> std::io::Result<libc::pid_t> {
+        let pid = libc::fork();
+        if pid < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(pid)
+    }
+
+    /// Closes every file except `keep`, so that the pipes of the program are closed
+    /// once it exits and the parent is not kept waiting for `exec`.
+    unsafe fn close_files_except(keep: libc::c_int) {
+        for (first, last) in [(0, keep - 1), (keep + 1, libc::c_int::MAX)] {
+            if first <= last
+                && libc::syscall(
+                    libc::SYS_close_range,
+                    first as libc::c_uint,
+                    last as libc::c_uint,
+                    0,
+                ) < 0
+            {
+                for fd in first..=last.min(1023) {
+                    libc::close(fd);
+                }
+            }
+        }
+    }
+
+    unsafe fn wait_child(pid: libc::pid_t) -> libc::c_int {
+        let mut status = 0;
+        while libc::waitpid(pid, &mut status, 0) < 0 {
+            if *libc::__errno_location() != libc::EINTR {
+                libc::_exit(1);
+            }
+        }
+        status
+    }
+
+    /// Waits for the program in the init process and writes its wait status to `status_fd`.
+    unsafe fn report_and_exit(pid: libc::pid_t, status_fd: libc::c_int) -> ! {
+        close_files_except(status_fd);
+        let status = wait_child(pid);
+        libc::write(
+            status_fd,
+            &status as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>(),
+        );
+        libc::_exit(0);
+    }
+
+    /// Waits for the init process and exits with the wait status of the program.
+    unsafe fn wait_and_exit(pid: libc::pid_t, status_fd: libc::c_int) -> ! {
+        close_files_except(status_fd);
+        let mut status: libc::c_int = 0;
+        let size = libc::read(
+            status_fd,
+            &mut status as *mut libc::c_int as *mut libc::c_void,
+            std::mem::size_of::<libc::c_int>(),
+        );
+        wait_child(pid);
+        if size != std::mem::size_of::<libc::c_int>() as isize {
+            libc::_exit(1);
+        }
+        if libc::WIFSIGNALED(status) {
+            let signal = libc::WTERMSIG(status);
+            libc::signal(signal, libc::SIG_DFL);
+            libc::kill(libc::getpid(), signal);
+        }
+        libc::_exit(libc::WEXITSTATUS(status));
+    }
+
+    /// Mount points of the current mount namespace, from `/proc/self/mountinfo`.
+    fn mount_points() -> Result<Vec<CString>> {
+        let mount_info = std::fs::read_to_string("/proc/self/mountinfo")
+            .context("failed to read mount points for the sandbox")?;
+        mount_info
+            .lines()
+            .filter_map(|line| line.split(' ').nth(4))
+            .map(|mount_point| Ok(CString::new(unescape(mount_point))?))
+            .collect()
+    }
+
+    /// Spaces and a few other characters are escaped as octal numbers, e.g. `\040`.
+    fn unescape(path: &str) -> Vec<u8> {
+        let bytes = path.as_bytes();
+        let mut result = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let octal = bytes.get(i + 1..i + 4).and_then(|digits| {
+                std::str::from_utf8(digits)
+                    .ok()
+                    .and_then(|digits| u8::from_str_radix(digits, 8).ok())
+            });
+            match octal {
+                Some(byte) if bytes[i] == b'\\' => {
+                    result.push(byte);
+                    i += 4;
+                }
+                _ => {
+                    result.push(bytes[i]);
+                    i += 1;
+                }
+            }
+        }
+        result
+    }
+
+    fn stmt(code: u32, k: u32) -> libc::sock_filter {
+        unsafe { libc::BPF_STMT(code as u16, k) }
+    }
+
+    fn jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+        unsafe { libc::BPF_JUMP(code as u16, k, jt, jf) }
+    }
+
+    /// Offset of the lower half of the system call argument `index` in `seccomp_data`
+    /// (little endian).
+    const fn arg_offset(index: u32) -> u32 {
+        16 + 8 * index
+    }
+
+    /// Allows the system call if the lower half of its argument `index` is one of `values`.
+    fn allow_arg_values(
+        syscall: libc::c_long,
+        index: u32,
+        values: &[u32],
+    ) -> Vec<libc::sock_filter> {
+        let count = values.len() as u8;
+        let mut block = vec![
+            jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                syscall as u32,
+                0,
+                count + 3,
+            ),
+            stmt(
+                libc::BPF_LD | libc::BPF_W | libc::BPF_ABS,
+                arg_offset(index),
+            ),
+        ];
+        for (i, &value) in values.iter().enumerate() {
+            // jumps to the allowing return after the denying one
+            block.push(jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                value,
+                count - i as u8,
+                0,
+            ));
+        }
+        block.push(stmt(
+            libc::BPF_RET | libc::BPF_K,
+            libc::SECCOMP_RET_ERRNO | libc::EPERM as u32,
+        ));
+        block.push(stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ALLOW));
+        block
+    }
+
+    /// Allows the system calls in the allowlist, other system calls fail with `EPERM`.
+    ///
+    /// `clone` is only allowed for threads, `clone3` fails with `ENOSYS` as its flags can not be
+    /// inspected, so that libc falls back to `clone`, and `prctl` and `ioctl` are only allowed for
+    /// the operations in [`PRCTL_OPTIONS`] and [`IOCTL_REQUESTS`].
+    fn seccomp_filter() -> Vec<libc::sock_filter> {
+        const ARCH_OFFSET: u32 = 4;
+        const NR_OFFSET: u32 = 0;
+        let mut filter = vec![
+            stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, ARCH_OFFSET),
+            jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                AUDIT_ARCH,
+                1,
+                0,
+            ),
+            stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS),
+            stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, NR_OFFSET),
+        ];
+        for &syscall in SYSCALLS.iter().chain(ARCH_SYSCALLS) {
+            filter.push(jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                syscall as u32,
+                0,
+                1,
+            ));
+            filter.push(stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ALLOW));
+        }
+
+        // clone: denied with any namespace flag, otherwise allowed with `CLONE_THREAD`
+        filter.extend([
+            jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                libc::SYS_clone as u32,
+                0,
+                5,
+            ),
+            stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, arg_offset(0)),
+            jump(
+                libc::BPF_JMP | libc::BPF_JSET | libc::BPF_K,
+                CLONE_NEW_FLAGS as u32,
+                2,
+                0,
+            ),
+            jump(
+                libc::BPF_JMP | libc::BPF_JSET | libc::BPF_K,
+                libc::CLONE_THREAD as u32,
+                0,
+                1,
+            ),
+            stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ALLOW),
+            stmt(
+                libc::BPF_RET | libc::BPF_K,
+                libc::SECCOMP_RET_ERRNO | libc::EPERM as u32,
+            ),
+        ]);
+        filter.extend([
+            jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                libc::SYS_clone3 as u32,
+                0,
+                1,
+            ),
+            stmt(
+                libc::BPF_RET | libc::BPF_K,
+                libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32,
+            ),
+        ]);
+        let prctl_options = PRCTL_OPTIONS.iter().map(|&option| option as u32);
+        filter.extend(allow_arg_values(
+            libc::SYS_prctl,
+            0,
+            &prctl_options.collect::<Vec<_>>(),
+        ));
+        let ioctl_requests = IOCTL_REQUESTS.iter().map(|&request| request as u32);
+        filter.extend(allow_arg_values(
+            libc::SYS_ioctl,
+            1,
+            &ioctl_requests.collect::<Vec<_>>(),
+        ));
+
+        filter.push(stmt(
+            libc::BPF_RET | libc::BPF_K,
+            libc::SECCOMP_RET_ERRNO | libc::EPERM as u32,
+        ));
+        filter
+    }
+}
+
+#[cfg(all(
+    test,
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod tests {
+    use super::*;
+
+    /// Runs a shell script in the sandbox with `args` from `$0`,
+    /// `None` if namespaces are not permitted here.
+    fn run(
+        script: &str,
+        writable_dir: &std::path::Path,
+        args: &[&std::path::Path],
+    ) -> Option<std::process::Output> {
+        let sandboxed = |script: &str| {
+            let mut command = std::process::Command::new("/bin/sh");
+            command.arg("-c").arg(script).args(args);
+            apply(&mut command, Some(writable_dir)).unwrap();
+            command.current_dir(writable_dir).output()
+        };
+        match sandboxed("exit 0") {
+            Ok(output) if output.status.success() => Some(sandboxed(script).unwrap()),
+            _ => {
+                eprintln!("skipped, the sandbox is not permitted");
+                None
+            }
+        }
+    }
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("cptool-sandbox-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn forbidden_syscall() {
+        let dir = test_dir("syscall");
+        // a pipeline forks, which is denied by the seccomp filter
+        if let Some(output) = run("true | true", &dir, &[]) {
+            assert!(!output.status.success());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writable_dirs() {
+        let dir = test_dir("writable");
+        let outside = test_dir("outside");
+        // only builtins can run, as the sandbox can not fork
+        let script = format!(
+            "echo 1 > out && echo 2 > \"$TMPDIR/scratch\" && read a < out \
+             && read b < \"$TMPDIR/scratch\" && echo $a $b && ! echo 3 > {}/file",
+            outside.display()
+        );
+        if let Some(output) = run(&script, &dir, &[]) {
+            assert!(output.status.success(), "{:?}", output);
+            assert_eq!(output.stdout, b"1 2\n");
+            assert!(!outside.join("file").exists());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn hidden_dir() {
+        let dir = test_dir("hidden");
+        // the current directory, the crate here, is hidden except the files named by the command
+        let files = std::env::current_dir()
+            .unwrap()
+            .join("target")
+            .join(format!("cptool-sandbox-hidden-{}", std::process::id()));
+        std::fs::create_dir_all(&files).unwrap();
+        std::fs::write(files.join("exposed"), "1\n").unwrap();
+        std::fs::write(files.join("hidden"), "2\n").unwrap();
+        let script = format!(
+            "read a < \"$0\" && echo $a && ! read b < {}",
+            files.join("hidden").display()
+        );
+        if let Some(output) = run(&script, &dir, &[&files.join("exposed")]) {
+            assert!(output.status.success(), "{:?}", output);
+            assert_eq!(output.stdout, b"1\n");
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_dir_all(&files).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use super::problem::test::{TestTask, TestTaskType};
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Score of a task from the points of its test cases, without dependencies.
+///
+/// `sum` awards the average points and `min` the minimum points of the test cases.
+pub fn task_score(task: &TestTask, points: &[f64]) -> f64 {
+    if points.is_empty() {
+        return 0.0;
+    }
+    let ratio = match task.task_type {
+        TestTaskType::Sum => points.iter().sum::<f64>() / points.len() as f64,
+        TestTaskType::Min => points.iter().copied().fold(f64::INFINITY, f64::min),
+    };
+    task.score * ratio
+}
+
+/// Scores of tasks given the points of their test cases (in the same order as `tasks`).
+///
+/// A task scores zero if any of its dependencies, directly or indirectly, is not passed,
+/// that is, does not get full points on every test case.
+pub fn evaluate(tasks: &[TestTask], points: &[Vec<f64>]) -> Result<Vec<f64>> {
+    let task_id = tasks
+        .iter()
+        .enumerate()
+        .map(|(id, task)| (task.name.as_str(), id))
+        .collect::<HashMap<_, _>>();
+
+    /// Returns the score of the task and whether it is passed.
+    fn visit(
+        id: usize,
+        tasks: &[TestTask],
+        points: &[Vec<f64>],
+        task_id: &HashMap<&str, usize>,
+        results: &mut Vec<Option<(f64, bool)>>,
+        visiting: &mut Vec<bool>,
+    ) -> Result<(f64, bool)> {
+        if let Some(result) = results[id] {
+            return Ok(result);
+        }
+        if visiting[id] {
+            return Err(anyhow::anyhow!(
+                "circular dependency of task `{}`",
+                tasks[id].name
+            ));
+        }
+        visiting[id] = true;
+        let mut dependencies_passed = true;
+        for dependency in tasks[id].dependencies.iter() {
+            let &dependency_id = task_id
+                .get(dependency.as_str())
+                .ok_or_else(|| anyhow::anyhow!("task `{}` not found", dependency))?;
+            let (_, passed) = visit(dependency_id, tasks, points, task_id, results, visiting)?;
+            dependencies_passed &= passed;
+        }
+        let result = if dependencies_passed {
+            (
+                task_score(&tasks[id], &points[id]),
+                points[id].iter().all(|&point| point >= 1.0),
+            )
+        } else {
+            (0.0, false)
+        };
+        results[id] = Some(result);
+        Ok(result)
+    }
+
+    let mut results = vec![None; tasks.len()];
+    let mut visiting = vec![false; tasks.len()];
+    (0..tasks.len())
+        .map(|id| {
+            visit(id, tasks, points, &task_id, &mut results, &mut visiting).map(|(score, _)| score)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(name: &str, score: f64, task_type: TestTaskType, dependencies: &[&str]) -> TestTask {
+        TestTask {
+            name: name.to_string(),
+            score,
+            task_type,
+            bundles: vec![],
+            dependencies: dependencies.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn sum_and_min() {
+        let tasks = [
+            task("sum", 40.0, TestTaskType::Sum, &[]),
+            task("min", 60.0, TestTaskType::Min, &[]),
+            task("empty", 10.0, TestTaskType::Sum, &[]),
+        ];
+        let scores = evaluate(&tasks, &[vec![1.0, 0.5], vec![1.0, 0.5], vec![]]).unwrap();
+        assert_eq!(scores, [30.0, 30.0, 0.0]);
+    }
+
+    #[test]
+    fn transitive_dependency_failure() {
+        let tasks = [
+            task("a", 20.0, TestTaskType::Min, &[]),
+            task("b", 30.0, TestTaskType::Min, &["a"]),
+            task("c", 50.0, TestTaskType::Min, &["b"]),
+        ];
+        let scores = evaluate(&tasks, &[vec![0.0], vec![1.0], vec![1.0]]).unwrap();
+        assert_eq!(scores, [0.0, 0.0, 0.0]);
+        let scores = evaluate(&tasks, &[vec![1.0], vec![1.0], vec![1.0]]).unwrap();
+        assert_eq!(scores, [20.0, 30.0, 50.0]);
+    }
+
+    #[test]
+    fn zero_score_dependency() {
+        let tasks = [
+            task("sample", 0.0, TestTaskType::Min, &[]),
+            task("main", 100.0, TestTaskType::Sum, &["sample"]),
+        ];
+        let scores = evaluate(&tasks, &[vec![0.0], vec![1.0]]).unwrap();
+        assert_eq!(scores, [0.0, 0.0]);
+        let scores = evaluate(&tasks, &[vec![1.0], vec![1.0]]).unwrap();
+        assert_eq!(scores, [0.0, 100.0]);
+    }
+
+    #[test]
+    fn partial_dependency() {
+        let tasks = [
+            task("a", 50.0, TestTaskType::Sum, &[]),
+            task("b", 50.0, TestTaskType::Sum, &["a"]),
+        ];
+        let scores = evaluate(&tasks, &[vec![1.0, 0.5], vec![1.0]]).unwrap();
+        assert_eq!(scores, [37.5, 0.0]);
+    }
+
+    #[test]
+    fn cycle() {
+        let tasks = [
+            task("a", 50.0, TestTaskType::Sum, &["b"]),
+            task("b", 50.0, TestTaskType::Sum, &["a"]),
+        ];
+        assert!(evaluate(&tasks, &[vec![1.0], vec![1.0]]).is_err());
+    }
+
+    #[test]
+    fn missing_dependency() {
+        let tasks = [task("a", 50.0, TestTaskType::Sum, &["b"])];
+        assert!(evaluate(&tasks, &[vec![1.0]]).is_err());
+    }
+}
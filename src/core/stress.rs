@@ -0,0 +1,114 @@
+use super::judge;
+use super::problem::test::{TestCase, Verdict, SEED_ENV};
+use super::problem::FileIo;
+use super::program::Program;
+use anyhow::{Context, Result};
+
+pub struct Stress<'a> {
+    pub generator: &'a Program,
+    /// Arguments to the generator, followed by the seed, which is also set as `CPTOOL_SEED`.
+    pub args: Vec<String>,
+    pub solution: &'a Program,
+    pub brute: &'a Program,
+    pub validator: Option<&'a Program>,
+    pub checker: Option<&'a Program>,
+    pub interactor: Option<&'a Program>,
+    pub file_io: Option<&'a FileIo>,
+}
+
+impl Stress<'_> {
+    fn run_once(
+        &self,
+        seed: u64,
+        case: &TestCase,
+        output_path: &std::path::Path,
+    ) -> Result<judge::CaseResult> {
+        let args = self
+            .args
+            .iter()
+            .cloned()
+            .chain(std::iter::once(seed.to_string()))
+            .collect::<Vec<_>>();
+        let input = std::fs::File::create(&case.input_path)?;
+        self.generator
+            .execute_in(
+                args,
+                None,
+                &[(SEED_ENV, seed.to_string())],
+                None,
+                Some(input),
+            )
+            .context("failed to generate data")?;
+
+        if let Some(validator) = self.validator {
+            let input = std::fs::File::open(&case.input_path)?;
+            validator
+                .execute(vec![], Some(input), None)
+                .context("failed to validate data")?;
+        }
+
+        let run = judge::run_solution(
+            self.brute,
+            self.interactor,
+            self.file_io,
+            &case.input_path,
+            &case.answer_path,
+        )
+        .context("failed to generate answer with brute force")?;
+        if let Some(failure) = run.failure() {
+            return Err(anyhow::anyhow!("{}: {}", failure, self.brute))
+                .context("failed to generate answer with brute force");
+        }
+
+        judge::judge_case(
+            case,
+            self.solution,
+            self.interactor,
+            self.file_io,
+            self.checker,
+            output_path,
+        )
+    }
+
+    /// Runs with each seed until the solution is not accepted, and returns the failing seed.
+    ///
+    /// The input, the answer of the brute force and the output of the solution of the failing
+    /// run are saved as `<seed>.in`, `<seed>.ans` and `<seed>.out` in `failed_dir`,
+    /// along with the log of the run as `<seed>.log`.
+    pub fn run(
+        &self,
+        seeds: std::ops::Range<u64>,
+        failed_dir: &std::path::Path,
+    ) -> Result<Option<u64>> {
+        let temp_dir = crate::utils::temp_dir().join("stress");
+        std::fs::create_dir_all(&temp_dir)?;
+        let case = TestCase {
+            args: self.args.clone(),
+            input_path: temp_dir.join("stress.in"),
+            answer_path: temp_dir.join("stress.ans"),
+        };
+        let output_path = temp_dir.join("stress.out");
+
+        for seed in seeds {
+            let result = self
+                .run_once(seed, &case, &output_path)
+                .with_context(|| format!("stress failed with seed {}", seed))?;
+            if result.check.verdict == Verdict::Accepted {
+                println!("seed {}: {}", seed, result.check.verdict);
+                continue;
+            }
+            println!("seed {}: {}", seed, result.check);
+
+            std::fs::create_dir_all(failed_dir)?;
+            std::fs::copy(&case.input_path, failed_dir.join(format!("{}.in", seed)))?;
+            std::fs::copy(&case.answer_path, failed_dir.join(format!("{}.ans", seed)))?;
+            std::fs::copy(&output_path, failed_dir.join(format!("{}.out", seed)))?;
+            std::fs::copy(
+                output_path.with_extension("log"),
+                failed_dir.join(format!("{}.log", seed)),
+            )?;
+            return Ok(Some(seed));
+        }
+        Ok(None)
+    }
+}
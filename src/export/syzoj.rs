@@ -10,6 +10,10 @@ pub enum ProgramType {
     // TOOD: cpp11, cpp14, cpp17
     #[serde(rename = "cpp")]
     Cpp,
+    #[serde(rename = "python3")]
+    Python3,
+    #[serde(rename = "rust")]
+    Rust,
     // TODO: add more
 }
 
@@ -48,8 +52,18 @@ pub struct Subtask {
     pub dependencies: Option<Vec<usize>>,
 }
 
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum ProblemType {
+    #[serde(rename = "traditional")]
+    Traditional,
+    #[serde(rename = "interaction")]
+    Interaction,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: ProblemType,
     #[serde(rename = "inputFile")]
     pub input_file: Option<String>,
     #[serde(rename = "outputFile")]
@@ -61,6 +75,51 @@ pub struct Problem {
 
     #[serde(rename = "specialJudge")]
     pub special_judge: Option<Program>,
+    pub interactor: Option<Program>,
+}
+
+fn export_program(
+    program: &crate::core::program::Program,
+    role: &str,
+    file_stem: &str,
+    export_dir: &std::path::Path,
+) -> Result<Program> {
+    let (language, source_path, extension) = match &program.info {
+        crate::core::program::ProgramInfo::Command(_) => Err(anyhow::anyhow!(
+            "command program is not supported as {} in syzoj exporter",
+            role
+        )),
+        crate::core::program::ProgramInfo::Custom(_) => Err(anyhow::anyhow!(
+            "custom program is not supported as {} in syzoj exporter",
+            role
+        )),
+        crate::core::program::ProgramInfo::Java(_)
+        | crate::core::program::ProgramInfo::Kotlin(_) => Err(anyhow::anyhow!(
+            "jvm program is not supported as {} in syzoj exporter",
+            role
+        )),
+        crate::core::program::ProgramInfo::Cpp(program) => {
+            Ok((ProgramType::Cpp, &program.source_path, "cpp"))
+        }
+        crate::core::program::ProgramInfo::Python(program) => {
+            Ok((ProgramType::Python3, &program.path, "py"))
+        }
+        crate::core::program::ProgramInfo::Rust(program) => {
+            if program.source_path.is_dir() {
+                return Err(anyhow::anyhow!(
+                    "cargo project is not supported as {} in syzoj exporter",
+                    role
+                ));
+            }
+            Ok((ProgramType::Rust, &program.source_path, "rs"))
+        }
+    }?;
+    let name = format!("{}.{}", file_stem, extension);
+    std::fs::copy(source_path, export_dir.join(&name))?;
+    Ok(Program {
+        language,
+        file_name: name,
+    })
 }
 
 pub struct SyzojExporter;
@@ -73,6 +132,22 @@ impl Exporter for SyzojExporter {
             task_id.insert(&task.name, i);
         });
 
+        // with file io, data files are named after the input file, e.g. `sum#.in` and `sum#.ans`
+        let (input_file, output_file, answer_file) = match &problem.file_io {
+            Some(file_io) => {
+                let stem = std::path::Path::new(&file_io.input)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                (
+                    format!("{}#.in", stem),
+                    format!("{}#.ans", stem),
+                    Some(file_io.output.clone()),
+                )
+            }
+            None => ("#.in".to_string(), "#.ans".to_string(), None),
+        };
+
         let mut counter = 0usize;
         let subtasks = problem
             .test
@@ -92,8 +167,8 @@ impl Exporter for SyzojExporter {
                             .iter()
                             .map(|case| {
                                 let name = format!("{}", counter);
-                                let input_path = export_dir.join(format!("{}.in", name));
-                                let answer_path = export_dir.join(format!("{}.ans", name));
+                                let input_path = export_dir.join(input_file.replace('#', &name));
+                                let answer_path = export_dir.join(output_file.replace('#', &name));
                                 counter += 1;
                                 std::fs::copy(&case.input_path, input_path)?;
                                 std::fs::copy(&case.answer_path, answer_path)?;
@@ -124,31 +199,30 @@ impl Exporter for SyzojExporter {
             })
             .collect::<Result<Vec<_>>>()?;
 
-        let special_judge = if let Some(checker) = &problem.checker {
-            match &checker.info {
-                crate::core::program::ProgramInfo::Command(_) => Err(anyhow::anyhow!(
-                    "command program is not supported as special judge in syzoj exporter"
-                )),
-                crate::core::program::ProgramInfo::Cpp(program) => {
-                    let name = "spj.cpp".to_string();
-                    let path = export_dir.join(&name);
-                    std::fs::copy(&program.source_path, path)?;
-                    Ok(Some(Program {
-                        language: ProgramType::Cpp,
-                        file_name: name,
-                    }))
-                }
-            }?
+        let special_judge = problem
+            .checker
+            .as_ref()
+            .map(|checker| export_program(checker, "special judge", "spj", export_dir))
+            .transpose()?;
+        let interactor = problem
+            .interactor
+            .as_ref()
+            .map(|interactor| export_program(interactor, "interactor", "interactor", export_dir))
+            .transpose()?;
+        let problem_type = if interactor.is_some() {
+            ProblemType::Interaction
         } else {
-            None
+            ProblemType::Traditional
         };
 
         let result = Problem {
-            input_file: Some("#.in".to_string()),
-            output_file: Some("#.ans".to_string()),
-            answer_file: None,
+            problem_type,
+            input_file: Some(input_file),
+            output_file: Some(output_file),
+            answer_file,
             subtasks,
             special_judge,
+            interactor,
         };
 
         let yaml = serde_yaml::to_string(&result)?;
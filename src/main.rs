@@ -1,5 +1,6 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use cptool::config::problem as config_problem;
+use cptool::core::{judge, stress};
 use cptool::export::{syzoj, Exporter, OnlineJudge};
 use std::time::Instant;
 
@@ -17,6 +18,61 @@ struct Args {
 
     #[arg(long, default_value = "./export")]
     export_dir: Option<std::path::PathBuf>,
+
+    /// Only regenerate test cases whose generator, arguments, solution or validator changed
+    #[arg(short, long)]
+    incremental: bool,
+
+    /// Run each generator twice and fail if the inputs differ, the second time in the same or another working directory.
+    /// Every test case is regenerated, even with --incremental
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "same-dir")]
+    check_determinism: Option<config_problem::DeterminismCheck>,
+
+    /// Maximum number of programs compiled or test cases generated in parallel, default to the number of CPUs
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Judge solutions on the generated test data
+    Test {
+        /// Program names of the solutions, default to the model solution and the declared solutions
+        solutions: Vec<String>,
+    },
+    /// Compare a solution with a brute force on generated data until they differ
+    Stress {
+        /// Program name of the generator, which gets the seed as the last argument
+        #[arg(short, long)]
+        generator: String,
+
+        /// Program name of the brute force
+        #[arg(short, long)]
+        brute: String,
+
+        /// Program name of the solution, default to the model solution
+        #[arg(short, long)]
+        solution: Option<String>,
+
+        /// Number of runs
+        #[arg(short = 'n', long, default_value_t = 100)]
+        iterations: u64,
+
+        /// Seed of the first run, incremented for each run
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+
+        /// Directory to save the failing data
+        #[arg(long, default_value = "./stress")]
+        failed_dir: std::path::PathBuf,
+
+        /// Arguments to the generator, before the seed
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -24,24 +80,118 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let start = Instant::now();
 
+    let jobs = args.jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|jobs| jobs.get())
+            .unwrap_or(1)
+    });
+
     std::env::set_current_dir(&args.work_dir)?;
 
     let problem_yaml = std::fs::read_to_string("problem.yaml")?;
     let problem_config: config_problem::Problem = serde_yaml::from_str(&problem_yaml)?;
 
-    let problem = problem_config.generate(&args.output_dir)?;
+    if let Some(Command::Stress {
+        generator,
+        brute,
+        solution,
+        iterations,
+        seed,
+        failed_dir,
+        args,
+    }) = &args.command
+    {
+        let seeds = *seed..seed.checked_add(*iterations).ok_or_else(|| {
+            anyhow::anyhow!("seed {} plus {} iterations overflows", seed, iterations)
+        })?;
+        let file_io = problem_config.generate_file_io()?;
+        let programs = problem_config.generate_programs(jobs)?;
+        let get_program = |name: &String| {
+            programs
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("program `{}` not found", name))
+        };
+        let stress = stress::Stress {
+            generator: get_program(generator)?,
+            args: args.clone(),
+            solution: get_program(solution.as_ref().unwrap_or(&problem_config.solution_name))?,
+            brute: get_program(brute)?,
+            validator: problem_config
+                .validator_name
+                .as_ref()
+                .map(get_program)
+                .transpose()?,
+            checker: problem_config
+                .checker_name
+                .as_ref()
+                .map(get_program)
+                .transpose()?,
+            interactor: problem_config
+                .interactor_name
+                .as_ref()
+                .map(get_program)
+                .transpose()?,
+            file_io: file_io.as_ref(),
+        };
+        if let Some(seed) = stress.run(seeds, failed_dir)? {
+            return Err(anyhow::anyhow!(
+                "solution failed with seed {}, data saved to `{}`",
+                seed,
+                failed_dir.display()
+            )
+            .into());
+        }
+    } else {
+        let options = config_problem::GenerateOptions {
+            incremental: args.incremental,
+            check_determinism: args.check_determinism,
+            jobs,
+        };
+        let problem = problem_config.generate(&args.output_dir, &options)?;
+
+        if let Some(OnlineJudge::Syzoj) = args.export_oj {
+            let export_dir = args
+                .export_dir
+                .expect("export path not specified")
+                .join("syzoj");
+            if export_dir.exists() {
+                std::fs::remove_dir_all(&export_dir)?;
+            }
+            std::fs::create_dir_all(&export_dir)?;
 
-    if let Some(OnlineJudge::Syzoj) = args.export_oj {
-        let export_dir = args
-            .export_dir
-            .expect("export path not specified")
-            .join("syzoj");
-        if export_dir.exists() {
-            std::fs::remove_dir_all(&export_dir)?;
+            syzoj::SyzojExporter::export(&problem, &export_dir)?;
         }
-        std::fs::create_dir_all(&export_dir)?;
 
-        syzoj::SyzojExporter::export(&problem, &export_dir)?;
+        if let Some(Command::Test { solutions }) = &args.command {
+            let solutions = if solutions.is_empty() {
+                let mut declared = problem
+                    .solutions
+                    .keys()
+                    .filter(|&name| name != &problem.solution_name)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                declared.sort();
+                std::iter::once(problem.solution_name.clone())
+                    .chain(declared)
+                    .collect()
+            } else {
+                solutions.clone()
+            };
+            let mut unexpected = vec![];
+            for solution in solutions.iter() {
+                let result = judge::judge(&problem, solution)?;
+                print!("{}", result);
+                if let Some(declared) = problem.solutions.get(solution) {
+                    unexpected.extend(result.unexpected(&declared.expected));
+                }
+            }
+            if !unexpected.is_empty() {
+                for message in unexpected.iter() {
+                    println!("unexpected verdict: {}", message);
+                }
+                return Err(anyhow::anyhow!("{} unexpected verdict(s)", unexpected.len()).into());
+            }
+        }
     }
 
     let elapsed = start.elapsed();
@@ -1,3 +1,196 @@
 pub fn temp_dir() -> std::path::PathBuf {
     "./tmp".into()
 }
+
+/// Absolute path of `path`, so that it stays valid when a program runs in another directory.
+pub fn absolute_path(path: &std::path::Path) -> std::path::PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// User-level toolchain file, `$CPTOOL_TOOLCHAINS` or `~/.config/cptool/toolchains.yaml`.
+pub fn toolchains_file() -> Option<std::path::PathBuf> {
+    if let Some(path) = std::env::var_os("CPTOOL_TOOLCHAINS") {
+        return Some(path.into());
+    }
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".config"))
+        })?;
+    Some(config_dir.join("cptool").join("toolchains.yaml"))
+}
+
+/// Compile cache directory, `$CPTOOL_CACHE_DIR` or `~/.cache/cptool`; an empty `$CPTOOL_CACHE_DIR` disables caching.
+pub fn cache_dir() -> Option<std::path::PathBuf> {
+    if let Some(path) = std::env::var_os("CPTOOL_CACHE_DIR") {
+        return (!path.is_empty()).then(|| path.into());
+    }
+    let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".cache"))
+        })?;
+    Some(cache_dir.join("cptool"))
+}
+
+/// Calls `f` on each item with at most `jobs` threads, and returns the results in order.
+pub fn parallel_map<T: Send, R: Send>(
+    jobs: usize,
+    items: Vec<T>,
+    f: impl Fn(T) -> R + Sync,
+) -> Vec<R> {
+    let jobs = jobs.clamp(1, items.len().max(1));
+    let queue = std::sync::Mutex::new(items.into_iter().enumerate());
+    let results = std::sync::Mutex::new(vec![]);
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                // the lock is released before the job runs
+                let next = queue.lock().unwrap().next();
+                let Some((index, item)) = next else {
+                    break;
+                };
+                let result = f(item);
+                results.lock().unwrap().push((index, result));
+            });
+        }
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Calls `f` on each job with at most `jobs` threads, where `f` can queue more jobs with the
+/// function it is given, and returns when every job is done.
+pub fn parallel_queue<T: Send>(jobs: usize, items: Vec<T>, f: impl Fn(T, &dyn Fn(T)) + Sync) {
+    struct State<T> {
+        queue: std::collections::VecDeque<T>,
+        running: usize,
+    }
+    /// Marks a job as done even if it panics, so that the other threads do not wait forever.
+    struct Running<'a, T>(&'a std::sync::Mutex<State<T>>, &'a std::sync::Condvar);
+    impl<T> Drop for Running<'_, T> {
+        fn drop(&mut self) {
+            let mut state = self.0.lock().unwrap_or_else(|error| error.into_inner());
+            state.running -= 1;
+            self.1.notify_all();
+        }
+    }
+
+    let state = std::sync::Mutex::new(State {
+        queue: items.into(),
+        running: 0,
+    });
+    let changed = std::sync::Condvar::new();
+    let push = |item| {
+        state.lock().unwrap().queue.push_back(item);
+        changed.notify_one();
+    };
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| loop {
+                let item = {
+                    let mut state = state.lock().unwrap();
+                    loop {
+                        if let Some(item) = state.queue.pop_front() {
+                            state.running += 1;
+                            break item;
+                        }
+                        // running jobs may still queue more
+                        if state.running == 0 {
+                            return;
+                        }
+                        state = changed.wait(state).unwrap();
+                    }
+                };
+                let _running = Running(&state, &changed);
+                f(item, &push);
+            });
+        }
+    });
+}
+
+/// FNV-1a, whose output is stable across runs unlike `DefaultHasher`.
+///
+/// Values hashed through `std::hash::Hash` may be fed differently by other Rust versions,
+/// which is fine for fingerprints that only cause a regeneration when they change.
+pub struct StableHasher(u64);
+
+impl StableHasher {
+    pub fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::hash::Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel_map_keeps_order() {
+        let items = (0..100).collect::<Vec<u64>>();
+        let results = parallel_map(4, items, |item| {
+            // later items finish first
+            std::thread::sleep(std::time::Duration::from_micros(100 - item));
+            item * 2
+        });
+        assert_eq!(results, (0..100).map(|item| item * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn parallel_map_zero_jobs() {
+        assert_eq!(parallel_map(0, vec![1, 2, 3], |item| item + 1), [2, 3, 4]);
+    }
+
+    #[test]
+    fn parallel_map_empty() {
+        assert!(parallel_map(4, Vec::<i32>::new(), |item| item).is_empty());
+    }
+
+    #[test]
+    fn parallel_queue_runs_queued_jobs() {
+        let done = std::sync::Mutex::new(vec![]);
+        // each job n > 0 queues n - 1, so that jobs keep coming after the initial ones
+        parallel_queue(3, vec![3, 5], |item: u32, push| {
+            if item > 0 {
+                push(item - 1);
+            }
+            done.lock().unwrap().push(item);
+        });
+        let mut done = done.into_inner().unwrap();
+        done.sort();
+        assert_eq!(done, [0, 0, 1, 1, 2, 2, 3, 3, 4, 5]);
+    }
+
+    #[test]
+    fn parallel_queue_zero_jobs_and_empty() {
+        let count = std::sync::atomic::AtomicUsize::new(0);
+        parallel_queue(0, vec![2], |item: u32, push| {
+            if item > 0 {
+                push(item - 1);
+            }
+            count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        });
+        assert_eq!(count.into_inner(), 3);
+        parallel_queue(4, Vec::<u32>::new(), |_, _| unreachable!());
+    }
+}